@@ -8,4 +8,72 @@ pub enum ErrorCode {
     InvalidState,
     #[msg("Not enough funds: amount_in > src_balance.")]
     NotEnoughFunds,
+    #[msg("Signer is not the authority of this swap state.")]
+    InvalidAuthority,
+    #[msg("Source token account is not owned by the swap authority.")]
+    InvalidSourceOwner,
+    #[msg("Source token account mint does not match the swap input token.")]
+    InvalidSourceMint,
+    #[msg("Swap input mint does not match the output of the previous leg.")]
+    MintMismatch,
+    #[msg("Route does not end in the swap input token.")]
+    RouteNotClosed,
+    #[msg("Remaining accounts do not match the route header.")]
+    InvalidRoute,
+    #[msg("Phoenix market accounts do not match the market header.")]
+    InvalidPhoenixMarket,
+    #[msg("Jupiter program authority does not match the route id.")]
+    InvalidJupiterAuthority,
+    #[msg("Realized profit is below the minimum profit threshold.")]
+    ProfitBelowThreshold,
+    #[msg("start_swap must be followed by profit_or_revert in the same transaction.")]
+    MissingProfitCheck,
+    #[msg("Swap must run between start_swap and profit_or_revert.")]
+    SwapOutsideWindow,
+    #[msg("Split legs must draw 1-100 percent and only merge into settled slots.")]
+    InvalidSplit,
+    #[msg("flash_borrow must be followed by flash_repay in the same transaction.")]
+    MissingFlashRepay,
+    #[msg("The flash vault already has an outstanding loan.")]
+    FlashLoanActive,
+    #[msg("The flash vault has no outstanding loan.")]
+    NoFlashLoan,
+    #[msg("Trading is paused by the admin.")]
+    Paused,
+    #[msg("CPI target is not a known DEX program.")]
+    UnknownDexProgram,
+    #[msg("Raydium pool accounts do not match the AMM state.")]
+    InvalidRaydiumPool,
+    #[msg("Orca pool accounts do not match the token swap state.")]
+    InvalidOrcaPool,
+    #[msg("Meteora pool accounts do not match the pool state.")]
+    InvalidMeteoraPool,
+    #[msg("Route step cannot be quoted.")]
+    UnsupportedQuote,
+    #[msg("Quote ran past the supplied tick or bin arrays.")]
+    QuoteOutOfRange,
+    #[msg("Swap state has no swap in progress in the current slot.")]
+    StaleSwapState,
+    #[msg("Leg received less than its minimum amount out.")]
+    InsufficientLegOutput,
+    #[msg("Route has more legs than the program executes.")]
+    RouteTooLong,
+    #[msg("Route is empty or its branches do not merge into the final slot.")]
+    InvalidRouteShape,
+    #[msg("Leg token account is not held by the swap authority or its treasury.")]
+    InvalidTokenOwner,
+    #[msg("Legs trading the input token must use the start_swap source account.")]
+    InvalidSourceAccount,
+    #[msg("Lifinity AMM account could not be parsed.")]
+    InvalidLifinityPool,
+    #[msg("Flash loan fee cannot exceed 10000 bps.")]
+    InvalidFlashFee,
+    #[msg("Quote divided by zero pool state.")]
+    QuoteDivisionByZero,
+    #[msg("Leg must trade out of the account the previous leg paid into.")]
+    InvalidLegAccount,
+    #[msg("A flash loan must be swapped through its swap state before flash_repay.")]
+    MissingFlashSwap,
+    #[msg("The flash loan was borrowed for another swap state.")]
+    InvalidFlashBorrower,
 }
\ No newline at end of file
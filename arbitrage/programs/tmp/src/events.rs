@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct ArbitrageExecuted {
+    pub authority: Pubkey,
+    pub input_token: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub profit: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dex {
+    Orca,
+    Whirlpool,
+    Raydium,
+    MeteoraDlmm,
+    MeteoraDynamicAmm,
+    Phoenix,
+    Lifinity,
+    Jupiter,
+}
+
+#[event]
+pub struct SwapLegExecuted {
+    pub dex: Dex,
+    pub pool: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    // pool fee charged on `amount_in`, in the input mint
+    pub fee: u64,
+}
@@ -0,0 +1,106 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked};
+use anchor_lang::Discriminator;
+use crate::error::ErrorCode;
+use crate::instruction;
+
+// Instruction introspection keeps the split lifecycle honest: every `start_swap`
+// must be closed by a `profit_or_revert` for the same swap state later in the
+// transaction, and every swap leg must run while such a window is open. Flash
+// loans get the same treatment: every `flash_borrow` needs a later `flash_repay`,
+// with a `start_swap` or `arbitrage` of the borrowing swap state in between.
+
+enum Lifecycle {
+    Start,
+    End,
+    Atomic,
+}
+
+fn lifecycle_of(ix: &Instruction, swap_state: &Pubkey) -> Option<Lifecycle> {
+    if ix.program_id != crate::ID || !ix.accounts.iter().any(|meta| meta.pubkey == *swap_state) {
+        return None;
+    }
+    if ix.data.starts_with(&instruction::StartSwap::DISCRIMINATOR) {
+        Some(Lifecycle::Start)
+    } else if ix.data.starts_with(&instruction::ProfitOrRevert::DISCRIMINATOR) {
+        Some(Lifecycle::End)
+    } else if ix.data.starts_with(&instruction::Arbitrage::DISCRIMINATOR) {
+        Some(Lifecycle::Atomic)
+    } else {
+        None
+    }
+}
+
+/// Whether the first lifecycle instruction after `current` closes the swap window.
+fn closed_later(instructions: &AccountInfo, current: usize, swap_state: &Pubkey) -> bool {
+    let mut index = current + 1;
+    while let Ok(ix) = load_instruction_at_checked(index, instructions) {
+        match lifecycle_of(&ix, swap_state) {
+            Some(Lifecycle::End) => return true,
+            Some(_) => return false,
+            None => index += 1,
+        }
+    }
+    false
+}
+
+/// Whether the last lifecycle instruction before `current` opened the swap window.
+fn opened_earlier(instructions: &AccountInfo, current: usize, swap_state: &Pubkey) -> Result<bool> {
+    for index in (0..current).rev() {
+        let ix = load_instruction_at_checked(index, instructions)?;
+        match lifecycle_of(&ix, swap_state) {
+            Some(Lifecycle::Start) => return Ok(true),
+            Some(_) => return Ok(false),
+            None => {}
+        }
+    }
+    Ok(false)
+}
+
+pub fn require_profit_check_after(instructions: &AccountInfo, swap_state: &Pubkey) -> Result<()> {
+    let current = load_current_index_checked(instructions)? as usize;
+    require!(
+        closed_later(instructions, current, swap_state),
+        ErrorCode::MissingProfitCheck
+    );
+    Ok(())
+}
+
+pub fn require_inside_swap(instructions: &AccountInfo, swap_state: &Pubkey) -> Result<()> {
+    let current = load_current_index_checked(instructions)? as usize;
+    // legs run inside `arbitrage` are already bracketed by its own profit check
+    let current_ix = load_instruction_at_checked(current, instructions)?;
+    if let Some(Lifecycle::Atomic) = lifecycle_of(&current_ix, swap_state) {
+        return Ok(());
+    }
+    require!(
+        opened_earlier(instructions, current, swap_state)?
+            && closed_later(instructions, current, swap_state),
+        ErrorCode::SwapOutsideWindow
+    );
+    Ok(())
+}
+
+pub fn require_flash_repay_after(
+    instructions: &AccountInfo,
+    flash_vault: &Pubkey,
+    swap_state: &Pubkey
+) -> Result<()> {
+    let mut index = load_current_index_checked(instructions)? as usize + 1;
+    let mut swapped = false;
+    while let Ok(ix) = load_instruction_at_checked(index, instructions) {
+        if ix.program_id == crate::ID
+            && ix.data.starts_with(&instruction::FlashRepay::DISCRIMINATOR)
+            && ix.accounts.iter().any(|meta| meta.pubkey == *flash_vault)
+        {
+            require!(swapped, ErrorCode::MissingFlashSwap);
+            return Ok(());
+        }
+        if let Some(Lifecycle::Start | Lifecycle::Atomic) = lifecycle_of(&ix, swap_state) {
+            swapped = true;
+        }
+        index += 1;
+    }
+    err!(ErrorCode::MissingFlashRepay)
+}
@@ -1,8 +1,117 @@
 use anchor_lang::prelude::*;
+use jupiter_amm_interface::Swap;
+use crate::ArbitrageStep;
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct SwapData {
     pub instruction: u8,
     pub amount_in: u64,
     pub minimum_amount_out: u64,
-}
\ No newline at end of file
+}
+
+/// Anchor-encoded Lifinity v2 `swap` instruction.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LifinitySwapData {
+    pub discriminator: [u8; 8],
+    pub amount_in: u64,
+    pub minimum_amount_out: u64,
+}
+
+/// Phoenix `Swap` instruction followed by an `OrderPacket::ImmediateOrCancel`.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct PhoenixSwapData {
+    pub instruction: u8,
+    pub order_packet: u8,
+    pub side: u8,
+    pub price_in_ticks: Option<u64>,
+    pub num_base_lots: u64,
+    pub num_quote_lots: u64,
+    pub min_base_lots_to_fill: u64,
+    pub min_quote_lots_to_fill: u64,
+    pub self_trade_behavior: u8,
+    pub match_limit: Option<u64>,
+    pub client_order_id: u128,
+    pub use_only_deposited_funds: bool,
+    pub last_valid_slot: Option<u64>,
+    pub last_valid_unix_timestamp_in_seconds: Option<u64>,
+}
+
+/// Deserializable mirror of `jupiter_override::RoutePlanStep`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct JupiterRoutePlanStep {
+    pub swap: Swap,
+    pub percent: u8,
+    pub input_index: u8,
+    pub output_index: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct JupiterRoute {
+    pub id: u8,
+    pub route_plan: Vec<JupiterRoutePlanStep>,
+}
+
+/// Minimum realized profit, as an absolute amount and in basis points of the swap input.
+/// Both must be met.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq)]
+pub struct ProfitThreshold {
+    pub min_profit: u64,
+    pub min_profit_bps: u16,
+}
+
+impl ProfitThreshold {
+    pub fn required_profit(&self, swap_input: u64) -> u64 {
+        let bps_profit = (swap_input as u128) * (self.min_profit_bps as u128) / 10000;
+        self.min_profit.max(u64::try_from(bps_profit).unwrap_or(u64::MAX))
+    }
+
+    // an all-zero threshold falls back to the configured default
+    pub fn or(self, default: ProfitThreshold) -> ProfitThreshold {
+        if self == ProfitThreshold::default() { default } else { self }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct RouteLeg {
+    pub step: ArbitrageStep,
+    pub accounts_len: u8,
+    // share of the amount held at `input_index` this leg swaps, as in jupiter's route plan
+    pub percent: u8,
+    pub input_index: u8,
+    pub output_index: u8,
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn threshold(min_profit: u64, min_profit_bps: u16) -> ProfitThreshold {
+        ProfitThreshold { min_profit, min_profit_bps }
+    }
+
+    #[test]
+    fn required_profit_takes_the_larger_bound() {
+        assert_eq!(threshold(0, 50).required_profit(1_000_000), 5_000);
+        assert_eq!(threshold(10_000, 50).required_profit(1_000_000), 10_000);
+        assert_eq!(threshold(10, 0).required_profit(1_000_000), 10);
+    }
+
+    #[test]
+    fn required_profit_rounds_bps_down() {
+        assert_eq!(threshold(0, 1).required_profit(19_999), 1);
+        assert_eq!(threshold(0, 1).required_profit(9_999), 0);
+    }
+
+    #[test]
+    fn required_profit_saturates_at_u64_max() {
+        assert_eq!(threshold(0, u16::MAX).required_profit(u64::MAX), u64::MAX);
+        assert_eq!(threshold(0, 10_000).required_profit(u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn all_zero_threshold_falls_back_to_the_default() {
+        let default = threshold(100, 20);
+        assert!(ProfitThreshold::default().or(default) == default);
+        assert!(threshold(0, 5).or(default) == threshold(0, 5));
+        assert!(threshold(7, 0).or(default) == threshold(7, 0));
+    }
+}
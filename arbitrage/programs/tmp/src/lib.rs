@@ -1,19 +1,32 @@
 // File: src/lib.rs
 
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount};
+use anchor_lang::Bumps;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
 use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::set_return_data;
+use std::collections::BTreeSet;
 
 declare_id!("CRQXfRGq3wTkjt7JkqhojPLiKLYLjHPGLebnfiiQB46T");
 
 pub mod error;
+pub mod events;
+pub mod introspection;
 pub mod state;
 pub mod ix_data;
+pub mod quotes;
+pub mod route;
 pub mod swaps;
+pub mod treasury;
 
 use error::ErrorCode;
-use state::SwapState;
-use ix_data::SwapData;
+use introspection::{require_flash_repay_after, require_inside_swap, require_profit_check_after};
+use events::{ArbitrageExecuted, Dex, SwapLegExecuted};
+use state::{ArbitrageState, Config, FlashVault, SwapState, Treasury};
+use ix_data::{JupiterRoute, ProfitThreshold, RouteLeg, SwapData};
+use quotes::{quote_meteora_dlmm, quote_meteora_dynamic_amm, quote_raydium, quote_whirlpool, LegQuote};
+use route::{final_slot, leg_failed, route_slots, take_leg_accounts, RouteSlot};
+use treasury::Custody;
 pub use swaps::*;
 
 #[program]
@@ -22,77 +35,311 @@ pub mod arbitrage_bot {
 
     pub fn init_program(ctx: Context<InitSwapState>, input_token: Pubkey) -> Result<()> {
         let swap_state = &mut ctx.accounts.swap_state;
+        swap_state.authority = ctx.accounts.authority.key();
+        swap_state.bump = ctx.bumps.swap_state;
         swap_state.swap_input = 0;
         swap_state.is_valid = false;  
         swap_state.input_token = input_token;
         swap_state.current_token = input_token;
+
+        let arbitrage_state = &mut ctx.accounts.arbitrage_state;
+        arbitrage_state.authority = ctx.accounts.authority.key();
+        arbitrage_state.input_token = input_token;
+        arbitrage_state.bump = ctx.bumps.arbitrage_state;
         Ok(())
     }
 
     pub fn start_swap(ctx: Context<TokenAndSwapState>, swap_input: u64) -> Result<()> {
+        require_profit_check_after(&ctx.accounts.instructions, &ctx.accounts.swap_state.key())?;
+        let start_balance = ctx.accounts.src.amount;
+        require_gte!(start_balance, swap_input, ErrorCode::NotEnoughFunds);
+        ctx.accounts.swap_state.start(ctx.accounts.src.key(), start_balance, swap_input, Clock::get()?.slot);
+        Ok(())
+    }
+
+    /// Runs `start_swap`, every leg of `route` and `profit_or_revert` in one instruction.
+    pub fn arbitrage<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TokenAndSwapState<'info>>,
+        swap_input: u64,
+        route: Vec<RouteLeg>,
+        threshold: ProfitThreshold
+    ) -> Result<()> {
+        let start_balance = ctx.accounts.src.amount;
+        require_gte!(start_balance, swap_input, ErrorCode::NotEnoughFunds);
         let swap_state = &mut ctx.accounts.swap_state;
-        swap_state.start_balance = ctx.accounts.src.amount;
-        swap_state.swap_input = swap_input;
-        swap_state.is_valid = true;
+        swap_state.start(ctx.accounts.src.key(), start_balance, swap_input, Clock::get()?.slot);
+
+        execute_route(ctx.program_id, swap_state, ctx.remaining_accounts, route)?;
+
+        let swap_state = &mut ctx.accounts.swap_state;
+        swap_state.is_valid = false;
+        require_keys_eq!(
+            swap_state.current_token,
+            swap_state.input_token,
+            ErrorCode::RouteNotClosed
+        );
+        ctx.accounts.src.reload()?;
+        let threshold = threshold.or(ctx.accounts.config.default_threshold);
+        let profit = check_profit(&ctx.accounts.swap_state, ctx.accounts.src.amount, &threshold)?;
+        record_arbitrage(&mut ctx.accounts.arbitrage_state, &ctx.accounts.swap_state, profit);
         Ok(())
     }
 
-    pub fn execute_arbitrage(ctx: Context<ExecuteArbitrage>, path: Vec<ArbitrageStep>) -> Result<()> {
+    pub fn execute_arbitrage<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteArbitrage<'info>>,
+        route: Vec<RouteLeg>
+    ) -> Result<()> {
         let swap_state = &mut ctx.accounts.swap_state;
+        execute_route(ctx.program_id, swap_state, ctx.remaining_accounts, route)?;
+
+        // each leg chains its input mint onto the slot it draws from,
+        // so the route is a cycle only if it ends where it started
+        require_keys_eq!(
+            swap_state.current_token,
+            swap_state.input_token,
+            ErrorCode::RouteNotClosed
+        );
         
-        // Execute each step in the arbitrage path
-        for step in path {
-            match step {
-                ArbitrageStep::Orca(amount_in, minimum_amount_out) => {
-                    orca_swap(ctx.accounts.orca.clone(), amount_in, minimum_amount_out)?;
-                },
-                ArbitrageStep::Raydium(amount_in, minimum_amount_out) => {
-                    raydium_swap(ctx.accounts.raydium.clone(), amount_in, minimum_amount_out)?;
-                },
-                ArbitrageStep::Meteora(amount_in, minimum_amount_out) => {
-                    meteora_swap(ctx.accounts.meteora.clone(), amount_in, minimum_amount_out)?;
+        Ok(())
+    }
+
+    /// Quotes `route` for `amount_in` of the swap state's input token without swapping.
+    /// Takes the same route and leg accounts as `arbitrage` and returns the expected
+    /// amounts of every leg as a borsh `Vec<LegQuote>` through the return data.
+    pub fn quote_route<'info>(
+        ctx: Context<'_, '_, 'info, 'info, QuoteRoute<'info>>,
+        amount_in: u64,
+        route: Vec<RouteLeg>
+    ) -> Result<()> {
+        let input_token = ctx.accounts.swap_state.input_token;
+        let quotes = quote_legs(ctx.program_id, input_token, amount_in, ctx.remaining_accounts, route)?;
+        set_return_data(&quotes.try_to_vec()?);
+        Ok(())
+    }
+
+    pub fn init_flash_vault(ctx: Context<InitFlashVault>, fee_bps: u16) -> Result<()> {
+        require_gte!(10_000, fee_bps, ErrorCode::InvalidFlashFee);
+        let flash_vault = &mut ctx.accounts.flash_vault;
+        flash_vault.mint = ctx.accounts.mint.key();
+        flash_vault.vault_tokens = ctx.accounts.vault_tokens.key();
+        flash_vault.bump = ctx.bumps.flash_vault;
+        flash_vault.fee_bps = fee_bps;
+        Ok(())
+    }
+
+    /// Lends `amount` of the swap's input token into `src`. Must come before
+    /// `start_swap` and be repaid by a later `flash_repay` in the same transaction.
+    pub fn flash_borrow(ctx: Context<FlashLoan>, amount: u64) -> Result<()> {
+        require_flash_repay_after(
+            &ctx.accounts.instructions,
+            &ctx.accounts.flash_vault.key(),
+            &ctx.accounts.swap_state.key()
+        )?;
+        require!(!ctx.accounts.swap_state.is_valid, ErrorCode::InvalidState);
+        require!(ctx.accounts.flash_vault.borrowed == 0, ErrorCode::FlashLoanActive);
+
+        let mint = ctx.accounts.mint.key();
+        let seeds: &[&[u8]] = &[b"flash_vault", mint.as_ref(), &[ctx.accounts.flash_vault.bump]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault_tokens.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.src.to_account_info(),
+                    authority: ctx.accounts.flash_vault.to_account_info(),
                 },
-                ArbitrageStep::Phoenix(amount_in, minimum_amount_out) => {
-                    phoenix_swap(ctx.accounts.phoenix.clone(), amount_in, minimum_amount_out)?;
+                &[seeds],
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        let flash_vault = &mut ctx.accounts.flash_vault;
+        flash_vault.borrowed = amount;
+        flash_vault.borrower = ctx.accounts.swap_state.key();
+        ctx.accounts.swap_state.flash_fee = flash_vault.fee(amount);
+        Ok(())
+    }
+
+    pub fn flash_repay(ctx: Context<FlashLoan>) -> Result<()> {
+        let flash_vault = &ctx.accounts.flash_vault;
+        require!(flash_vault.borrowed > 0, ErrorCode::NoFlashLoan);
+        // the fee sits in the borrower's swap state, settle it there
+        require_keys_eq!(flash_vault.borrower, ctx.accounts.swap_state.key(), ErrorCode::InvalidFlashBorrower);
+        let fee = flash_vault.fee(flash_vault.borrowed);
+        let owed = flash_vault.borrowed.saturating_add(fee);
+        msg!("flash repay: {:?} (fee {:?})", owed, fee);
+
+        // a treasury-held `src` repays through the treasury PDA, as the swap legs do
+        let custody = Custody::of(&ctx.accounts.src_authority, &ctx.accounts.swap_state)?;
+        custody.with_signer(|signer| {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.accounts.src.to_account_info(),
+                        mint: ctx.accounts.mint.to_account_info(),
+                        to: ctx.accounts.vault_tokens.to_account_info(),
+                        authority: ctx.accounts.src_authority.to_account_info(),
+                    },
+                    signer,
+                ),
+                owed,
+                ctx.accounts.mint.decimals,
+            )
+        })?;
+
+        let flash_vault = &mut ctx.accounts.flash_vault;
+        flash_vault.borrowed = 0;
+        flash_vault.borrower = Pubkey::default();
+        flash_vault.total_fees = flash_vault.total_fees.saturating_add(fee);
+        ctx.accounts.swap_state.flash_fee = 0;
+        Ok(())
+    }
+
+    pub fn withdraw_flash_liquidity(ctx: Context<WithdrawFlashLiquidity>, amount: u64) -> Result<()> {
+        require!(ctx.accounts.flash_vault.borrowed == 0, ErrorCode::FlashLoanActive);
+        let mint = ctx.accounts.mint.key();
+        let seeds: &[&[u8]] = &[b"flash_vault", mint.as_ref(), &[ctx.accounts.flash_vault.bump]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault_tokens.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.flash_vault.to_account_info(),
                 },
-                ArbitrageStep::Lifinity(amount_in, minimum_amount_out) => {
-                    lifinity_swap(ctx.accounts.lifinity.clone(), amount_in, minimum_amount_out)?;
+                &[seeds],
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )
+    }
+
+    pub fn init_config(ctx: Context<InitConfig>, default_threshold: ProfitThreshold) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.paused = false;
+        config.default_threshold = default_threshold;
+        config.bump = ctx.bumps.config;
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<UpdateConfig>, paused: bool) -> Result<()> {
+        ctx.accounts.config.paused = paused;
+        msg!("paused: {:?}", paused);
+        Ok(())
+    }
+
+    pub fn set_admin(ctx: Context<UpdateConfig>, admin: Pubkey) -> Result<()> {
+        ctx.accounts.config.admin = admin;
+        Ok(())
+    }
+
+    pub fn set_default_threshold(ctx: Context<UpdateConfig>, default_threshold: ProfitThreshold) -> Result<()> {
+        ctx.accounts.config.default_threshold = default_threshold;
+        Ok(())
+    }
+
+    pub fn init_treasury(ctx: Context<InitTreasury>, operator: Pubkey) -> Result<()> {
+        let treasury = &mut ctx.accounts.treasury;
+        treasury.owner = ctx.accounts.owner.key();
+        treasury.operator = operator;
+        treasury.bump = ctx.bumps.treasury;
+        Ok(())
+    }
+
+    /// Hands the treasury to another operator, or to none with the default key.
+    /// Swap states still pointing at the treasury stop signing for it at once.
+    pub fn set_operator(ctx: Context<SetOperator>, operator: Pubkey) -> Result<()> {
+        ctx.accounts.treasury.operator = operator;
+        Ok(())
+    }
+
+    /// Points the swap state at a treasury naming its authority as operator,
+    /// or back at the authority's own token accounts when none is given.
+    pub fn set_treasury(ctx: Context<SetTreasury>) -> Result<()> {
+        let swap_state = &mut ctx.accounts.swap_state;
+        require!(!swap_state.is_valid, ErrorCode::InvalidState);
+        swap_state.treasury = ctx.accounts.treasury.as_ref().map_or(Pubkey::default(), |treasury| treasury.key());
+        Ok(())
+    }
+
+    /// Clears a swap the owner's client left open, e.g. when `profit_or_revert` never ran.
+    pub fn reset_swap_state(ctx: Context<ResetSwapState>) -> Result<()> {
+        let swap_state = &mut ctx.accounts.swap_state;
+        swap_state.is_valid = false;
+        swap_state.swap_input = 0;
+        swap_state.flash_fee = 0;
+        swap_state.current_token = swap_state.input_token;
+        Ok(())
+    }
+
+    /// Closes the swap state, returning its rent to the authority. The arbitrage
+    /// stats of the mint are kept unless `close_arbitrage_state` closes them too.
+    pub fn close_swap_state(ctx: Context<CloseSwapState>) -> Result<()> {
+        require!(!ctx.accounts.swap_state.is_active(Clock::get()?.slot), ErrorCode::InvalidState);
+        Ok(())
+    }
+
+    /// Deletes the per-mint arbitrage stats, returning their rent to the authority.
+    /// The next `init_program` for the mint starts them again from zero.
+    pub fn close_arbitrage_state(_ctx: Context<CloseArbitrageState>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn deposit(ctx: Context<TreasuryTransfer>, amount: u64) -> Result<()> {
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.owner_tokens.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.treasury_tokens.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
                 },
-                ArbitrageStep::Jupiter(amount_in, minimum_amount_out) => {
-                    jupiter_swap(ctx.accounts.jupiter.clone(), amount_in, minimum_amount_out)?;
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )
+    }
+
+    pub fn withdraw(ctx: Context<TreasuryTransfer>, amount: u64) -> Result<()> {
+        let owner = ctx.accounts.owner.key();
+        let seeds: &[&[u8]] = &[b"treasury", owner.as_ref(), &[ctx.accounts.treasury.bump]];
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.treasury_tokens.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.owner_tokens.to_account_info(),
+                    authority: ctx.accounts.treasury.to_account_info(),
                 },
-            }
-            
-            swap_state.current_token = step.get_output_token();
-        }
-        
-        Ok(())
+                &[seeds],
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )
     }
 
-    pub fn profit_or_revert(ctx: Context<TokenAndSwapState>, path: Vec<ArbitrageStep>) -> Result<()> {
+    pub fn profit_or_revert(ctx: Context<TokenAndSwapState>, threshold: ProfitThreshold) -> Result<()> {
         let swap_state = &mut ctx.accounts.swap_state;
+        // only a swap opened by `start_swap` in this slot may be recorded, and only once
+        require!(swap_state.is_active(Clock::get()?.slot), ErrorCode::StaleSwapState);
         swap_state.is_valid = false;
-    
-        let init_balance = swap_state.start_balance;
-        let final_balance = ctx.accounts.src.amount;
-        
-        let total_fees: u64 = path.iter()
-            .map(|step| calculate_fees(step, init_balance))
-            .sum();
-        
-        msg!(
-            "old = {:?}; new = {:?}; diff = {:?}; fees = {:?}", 
-            init_balance, 
-            final_balance, 
-            final_balance.saturating_sub(init_balance), 
-            total_fees
-        );
-        
-        require!(
-            final_balance > init_balance.saturating_add(total_fees), 
-            ErrorCode::NoProfit
+        require_keys_eq!(
+            swap_state.current_token,
+            swap_state.input_token,
+            ErrorCode::RouteNotClosed
         );
-    
+        // the start balance was read from this account
+        require_keys_eq!(ctx.accounts.src.key(), swap_state.src, ErrorCode::InvalidSourceAccount);
+        let threshold = threshold.or(ctx.accounts.config.default_threshold);
+        let profit = check_profit(swap_state, ctx.accounts.src.amount, &threshold)?;
+        record_arbitrage(&mut ctx.accounts.arbitrage_state, &ctx.accounts.swap_state, profit);
         Ok(())
     }
 
@@ -102,9 +349,36 @@ pub mod arbitrage_bot {
         amount_in: u64,
         minimum_amount_out: u64
     ) -> Result<()> {
-        let amount_in = prepare_swap(&ctx.accounts.swap_state)?;
+        let amount_in = prepare_swap(&ctx.accounts.swap_state, &ctx.accounts.user_src, &ctx.accounts.instructions)?;
+        let fee_rate = orca_fee_rate(ctx.accounts)?;
         _orca_swap(&ctx, amount_in, minimum_amount_out)?;
-        end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_dst)?;
+        let leg = end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_dst, Dex::Orca, ctx.accounts.token_swap.key(), fee_rate, minimum_amount_out)?;
+        emit_cpi!(leg);
+        Ok(())
+    }
+
+    pub fn whirlpool_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, WhirlpoolSwap<'info>>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        sqrt_price_limit: u128
+    ) -> Result<()> {
+        // the direction follows from which side of the pool holds the current token
+        let a_to_b = ctx.accounts.token_owner_account_a.mint == ctx.accounts.swap_state.current_token;
+        let user_src = match a_to_b {
+            true => &ctx.accounts.token_owner_account_a,
+            false => &ctx.accounts.token_owner_account_b,
+        };
+        let amount_in = prepare_swap(&ctx.accounts.swap_state, user_src, &ctx.accounts.instructions)?;
+        let fee_rate = whirlpool_fee_rate(ctx.accounts);
+        _whirlpool_swap(&ctx, amount_in, minimum_amount_out, sqrt_price_limit, a_to_b)?;
+        let pool = ctx.accounts.whirlpool.key();
+        let user_dst = match a_to_b {
+            true => &mut ctx.accounts.token_owner_account_b,
+            false => &mut ctx.accounts.token_owner_account_a,
+        };
+        let leg = end_swap(&mut ctx.accounts.swap_state, user_dst, Dex::Whirlpool, pool, fee_rate, minimum_amount_out)?;
+        emit_cpi!(leg);
         Ok(())
     }
 
@@ -113,91 +387,602 @@ pub mod arbitrage_bot {
         amount_in: u64,
         minimum_amount_out: u64
     ) -> Result<()> {
-        let amount_in = prepare_swap(&ctx.accounts.swap_state)?;
+        let amount_in = prepare_swap(&ctx.accounts.swap_state, &ctx.accounts.user_source_token_account, &ctx.accounts.instructions)?;
+        let fee_rate = raydium_fee_rate(ctx.accounts)?;
         _raydium_swap(&ctx, amount_in, minimum_amount_out)?;
-        end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_destination_token_account)?;
+        let leg = end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_destination_token_account, Dex::Raydium, ctx.accounts.amm.key(), fee_rate, minimum_amount_out)?;
+        emit_cpi!(leg);
         Ok(())
     }
 
-    pub fn meteora_swap<'info>(
-        ctx: Context<'_, '_, '_, 'info, MeteoraSwap<'info>>,
+    pub fn meteora_dlmm_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, MeteoraDlmmSwap<'info>>,
         amount_in: u64,
         minimum_amount_out: u64
     ) -> Result<()> {
-        let amount_in = prepare_swap(&ctx.accounts.swap_state)?;
-        _meteora_swap(&ctx, amount_in, minimum_amount_out)?;
-        end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_output_token_account)?;
+        let amount_in = prepare_swap(&ctx.accounts.swap_state, &ctx.accounts.user_token_in, &ctx.accounts.instructions)?;
+        // read before the swap moves the pool
+        let fee_rate = meteora_dlmm_fee_rate(ctx.accounts)?;
+        _meteora_dlmm_swap(&ctx, amount_in, minimum_amount_out)?;
+        let leg = end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_token_out, Dex::MeteoraDlmm, ctx.accounts.lb_pair.key(), fee_rate, minimum_amount_out)?;
+        emit_cpi!(leg);
+        Ok(())
+    }
+
+    pub fn meteora_dynamic_amm_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, MeteoraDynamicAmmSwap<'info>>,
+        amount_in: u64,
+        minimum_amount_out: u64
+    ) -> Result<()> {
+        let amount_in = prepare_swap(&ctx.accounts.swap_state, &ctx.accounts.user_source_token, &ctx.accounts.instructions)?;
+        let fee_rate = meteora_dynamic_amm_fee_rate(ctx.accounts);
+        _meteora_dynamic_amm_swap(&ctx, amount_in, minimum_amount_out)?;
+        let leg = end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_destination_token, Dex::MeteoraDynamicAmm, ctx.accounts.pool.key(), fee_rate, minimum_amount_out)?;
+        emit_cpi!(leg);
+        Ok(())
+    }
+
+    pub fn phoenix_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, PhoenixSwap<'info>>,
+        amount_in: u64,
+        minimum_amount_out: u64
+    ) -> Result<()> {
+        let amount_in = prepare_swap(&ctx.accounts.swap_state, &ctx.accounts.user_src, &ctx.accounts.instructions)?;
+        let fee_rate = phoenix_fee_rate(ctx.accounts)?;
+        _phoenix_swap(&ctx, amount_in, minimum_amount_out)?;
+        let leg = end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_dst, Dex::Phoenix, ctx.accounts.market.key(), fee_rate, minimum_amount_out)?;
+        emit_cpi!(leg);
+        Ok(())
+    }
+
+    pub fn lifinity_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, LifinitySwap<'info>>,
+        amount_in: u64,
+        minimum_amount_out: u64
+    ) -> Result<()> {
+        let amount_in = prepare_swap(&ctx.accounts.swap_state, &ctx.accounts.user_src, &ctx.accounts.instructions)?;
+        let fee_rate = lifinity_fee_rate(ctx.accounts)?;
+        _lifinity_swap(&ctx, amount_in, minimum_amount_out)?;
+        let leg = end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_dst, Dex::Lifinity, ctx.accounts.amm.key(), fee_rate, minimum_amount_out)?;
+        emit_cpi!(leg);
         Ok(())
     }
 
     pub fn jupiter_swap<'info>(
         ctx: Context<'_, '_, '_, 'info, JupiterSwap<'info>>,
         amount_in: u64,
-        minimum_amount_out: u64
+        minimum_amount_out: u64,
+        route: JupiterRoute
     ) -> Result<()> {
-        let amount_in = prepare_swap(&ctx.accounts.swap_state)?;
-        _jupiter_swap(&ctx, amount_in, minimum_amount_out)?;
-        end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_destination_token)?;
+        let amount_in = prepare_swap(&ctx.accounts.swap_state, &ctx.accounts.user_source_token, &ctx.accounts.instructions)?;
+        // the fees of the underlying pools are already reflected in the quoted output
+        let fee_rate = 0;
+        _jupiter_swap(&ctx, amount_in, minimum_amount_out, route)?;
+        let leg = end_swap(&mut ctx.accounts.swap_state, &mut ctx.accounts.user_destination_token, Dex::Jupiter, ctx.accounts.jupiter_program.key(), fee_rate, minimum_amount_out)?;
+        emit_cpi!(leg);
         Ok(())
     }
 }
 
 #[derive(Accounts)]
-#[instruction(bump: u8)]
+#[instruction(input_token: Pubkey)]
 pub struct InitSwapState<'info> {
     #[account(
         init,
-        payer = payer,
+        payer = authority,
         space = 8 + SwapState::LEN,
-        seeds = [b"swap_state"],
+        seeds = [b"swap_state", authority.key().as_ref(), input_token.as_ref()],
         bump
     )]
     pub swap_state: Account<'info, SwapState>,
+    // kept across `close_swap_state`, reopening the swap state carries the stats on
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + ArbitrageState::LEN,
+        seeds = [b"arbitrage_state", authority.key().as_ref(), input_token.as_ref()],
+        bump
+    )]
+    pub arbitrage_state: Account<'info, ArbitrageState>,
     #[account(mut)]
-    pub payer: Signer<'info>,
+    pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct TokenAndSwapState<'info> {
-    pub src: Account<'info, TokenAccount>,
-    #[account(mut, seeds=[b"swap_state"], bump)]
+    #[account(
+        constraint = swap_state.is_custodian(&src.owner) @ ErrorCode::InvalidSourceOwner,
+        constraint = src.mint == swap_state.input_token @ ErrorCode::InvalidSourceMint
+    )]
+    pub src: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        has_one = authority @ ErrorCode::InvalidAuthority
+    )]
     pub swap_state: Account<'info, SwapState>,
+    #[account(
+        mut,
+        seeds = [b"arbitrage_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = arbitrage_state.bump
+    )]
+    pub arbitrage_state: Account<'info, ArbitrageState>,
+    #[account(seeds = [b"config"], bump = config.bump, constraint = !config.paused @ ErrorCode::Paused)]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: This is not dangerous because we check the sysvar id
+    pub instructions: AccountInfo<'info>,
 }
 
 #[derive(Accounts)]
-pub struct ExecuteArbitrage<'info> {
+pub struct InitFlashVault<'info> {
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + FlashVault::LEN,
+        seeds = [b"flash_vault", mint.key().as_ref()],
+        bump
+    )]
+    pub flash_vault: Account<'info, FlashVault>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"flash_vault_tokens", mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = flash_vault,
+        token::token_program = token_program
+    )]
+    pub vault_tokens: InterfaceAccount<'info, TokenAccount>,
+    // there is one vault per mint, so only the admin may open it
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.admin == authority.key() @ ErrorCode::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FlashLoan<'info> {
+    #[account(
+        mut,
+        seeds = [b"flash_vault", mint.key().as_ref()],
+        bump = flash_vault.bump,
+        has_one = mint,
+        has_one = vault_tokens
+    )]
+    pub flash_vault: Account<'info, FlashVault>,
     #[account(mut)]
+    pub vault_tokens: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = swap_state.input_token @ ErrorCode::InvalidSourceMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = swap_state.is_custodian(&src.owner) @ ErrorCode::InvalidSourceOwner,
+        constraint = src.mint == swap_state.input_token @ ErrorCode::InvalidSourceMint
+    )]
+    pub src: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = src.owner @ ErrorCode::InvalidSourceOwner)]
+    /// CHECK: The swap authority or its treasury PDA, checked by `Custody::of`
+    pub src_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        has_one = authority @ ErrorCode::InvalidAuthority
+    )]
     pub swap_state: Account<'info, SwapState>,
-    pub orca: OrcaSwap<'info>,
-    pub raydium: RaydiumSwap<'info>,
-    pub meteora: MeteoraSwap<'info>,
-    pub phoenix: PhoenixSwap<'info>,
-    pub lifinity: LifinitySwap<'info>,
-    pub jupiter: JupiterSwap<'info>,
+    #[account(seeds = [b"config"], bump = config.bump, constraint = !config.paused @ ErrorCode::Paused)]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: This is not dangerous because we check the sysvar id
+    pub instructions: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFlashLiquidity<'info> {
+    #[account(
+        seeds = [b"flash_vault", mint.key().as_ref()],
+        bump = flash_vault.bump,
+        has_one = mint,
+        has_one = vault_tokens
+    )]
+    pub flash_vault: Account<'info, FlashVault>,
+    #[account(mut)]
+    pub vault_tokens: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::mint = mint)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    // the liquidity answers to the current admin, so `set_admin` hands it over too
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = config.admin == authority.key() @ ErrorCode::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct InitConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Config::LEN,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    // only the upgrade authority may claim the config
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::ArbitrageBot>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::InvalidAuthority)]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ ErrorCode::InvalidAuthority
+    )]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitTreasury<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Treasury::LEN,
+        seeds = [b"treasury", owner.key().as_ref()],
+        bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetOperator<'info> {
+    #[account(
+        mut,
+        seeds = [b"treasury", owner.key().as_ref()],
+        bump = treasury.bump,
+        has_one = owner @ ErrorCode::InvalidAuthority
+    )]
+    pub treasury: Account<'info, Treasury>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetTreasury<'info> {
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        has_one = authority @ ErrorCode::InvalidAuthority
+    )]
+    pub swap_state: Account<'info, SwapState>,
+    #[account(constraint = treasury.operator == authority.key() @ ErrorCode::InvalidAuthority)]
+    pub treasury: Option<Account<'info, Treasury>>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResetSwapState<'info> {
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        has_one = authority @ ErrorCode::InvalidAuthority
+    )]
+    pub swap_state: Account<'info, SwapState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSwapState<'info> {
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        has_one = authority @ ErrorCode::InvalidAuthority
+    )]
+    pub swap_state: Account<'info, SwapState>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseArbitrageState<'info> {
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"arbitrage_state", arbitrage_state.authority.as_ref(), arbitrage_state.input_token.as_ref()],
+        bump = arbitrage_state.bump,
+        has_one = authority @ ErrorCode::InvalidAuthority
+    )]
+    pub arbitrage_state: Account<'info, ArbitrageState>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TreasuryTransfer<'info> {
+    #[account(
+        seeds = [b"treasury", owner.key().as_ref()],
+        bump = treasury.bump,
+        has_one = owner @ ErrorCode::InvalidAuthority
+    )]
+    pub treasury: Account<'info, Treasury>,
+    #[account(mut, token::mint = mint, token::authority = treasury)]
+    pub treasury_tokens: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = mint, token::authority = owner)]
+    pub owner_tokens: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub owner: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteArbitrage<'info> {
+    // the legs update the swap state through their own copy of this account,
+    // `execute_route` reloads it after every leg before this context exits
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        has_one = authority @ ErrorCode::InvalidAuthority
+    )]
+    pub swap_state: Account<'info, SwapState>,
+    #[account(seeds = [b"config"], bump = config.bump, constraint = !config.paused @ ErrorCode::Paused)]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+    // the accounts of every leg follow in `remaining_accounts`,
+    // sliced according to `RouteLeg::accounts_len`
+}
+
+#[derive(Accounts)]
+pub struct QuoteRoute<'info> {
+    #[account(
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump
+    )]
+    pub swap_state: Account<'info, SwapState>,
+    // the leg accounts follow in `remaining_accounts` as for `arbitrage`,
+    // only whirlpool, raydium and meteora legs can be quoted
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
 pub enum ArbitrageStep {
     Orca(u64, u64),
+    Whirlpool(u64, u64, u128),
     Raydium(u64, u64),
-    Meteora(u64, u64),
+    MeteoraDlmm(u64, u64),
+    MeteoraDynamicAmm(u64, u64),
     Phoenix(u64, u64),
     Lifinity(u64, u64),
-    Jupiter(u64, u64),
+    Jupiter(u64, u64, JupiterRoute),
 }
 
-impl ArbitrageStep {
-    fn get_output_token(&self) -> Pubkey {
-        // Implementation would return the output token for each DEX step
-        // This is a placeholder that should be replaced with actual token addresses
-        Pubkey::default()
+// Helper functions
+pub fn execute_route<'info>(
+    program_id: &Pubkey,
+    swap_state: &mut Account<'info, SwapState>,
+    mut remaining_accounts: &'info [AccountInfo<'info>],
+    route: Vec<RouteLeg>
+) -> Result<()> {
+    let mut slots = route_slots(&route, swap_state.current_token, swap_state.current_account, swap_state.swap_input)?;
+
+    // Execute each leg against its own slice of the remaining accounts
+    for (index, leg) in route.iter().enumerate() {
+        execute_route_leg(program_id, swap_state, &mut slots, &mut remaining_accounts, leg.clone())
+            .map_err(|err| leg_failed(index, err))?;
     }
+    require!(remaining_accounts.is_empty(), ErrorCode::InvalidRoute);
+
+    let final_slot = final_slot(&slots, &route)?;
+    swap_state.swap_input = final_slot.amount;
+    swap_state.current_token = final_slot.mint;
+    swap_state.current_account = final_slot.account;
+    swap_state.exit(program_id)
 }
 
-// Helper functions
-pub fn prepare_swap(swap_state: &Account<SwapState>) -> Result<u64> {
-    require!(swap_state.is_valid, ErrorCode::InvalidState);
+fn execute_route_leg<'info>(
+    program_id: &Pubkey,
+    swap_state: &mut Account<'info, SwapState>,
+    slots: &mut [RouteSlot],
+    remaining_accounts: &mut &'info [AccountInfo<'info>],
+    leg: RouteLeg
+) -> Result<()> {
+    let leg_accounts = take_leg_accounts(remaining_accounts, &leg)?;
+
+    // hand the leg its share of the input slot through the swap state,
+    // which the leg deserializes from the account data
+    let (input_index, output_index) = (leg.input_index as usize, leg.output_index as usize);
+    swap_state.swap_input = slots[input_index].draw(leg.percent)?;
+    swap_state.current_token = slots[input_index].mint;
+    swap_state.current_account = slots[input_index].account;
+    swap_state.exit(program_id)?;
+
+    match leg.step {
+        ArbitrageStep::Orca(amount_in, minimum_amount_out) => {
+            execute_leg::<OrcaSwap>(program_id, leg_accounts, |ctx| {
+                arbitrage_bot::orca_swap(ctx, amount_in, minimum_amount_out)
+            })?;
+        },
+        ArbitrageStep::Whirlpool(amount_in, minimum_amount_out, sqrt_price_limit) => {
+            execute_leg::<WhirlpoolSwap>(program_id, leg_accounts, |ctx| {
+                arbitrage_bot::whirlpool_swap(ctx, amount_in, minimum_amount_out, sqrt_price_limit)
+            })?;
+        },
+        ArbitrageStep::Raydium(amount_in, minimum_amount_out) => {
+            execute_leg::<RaydiumSwap>(program_id, leg_accounts, |ctx| {
+                arbitrage_bot::raydium_swap(ctx, amount_in, minimum_amount_out)
+            })?;
+        },
+        ArbitrageStep::MeteoraDlmm(amount_in, minimum_amount_out) => {
+            execute_leg::<MeteoraDlmmSwap>(program_id, leg_accounts, |ctx| {
+                arbitrage_bot::meteora_dlmm_swap(ctx, amount_in, minimum_amount_out)
+            })?;
+        },
+        ArbitrageStep::MeteoraDynamicAmm(amount_in, minimum_amount_out) => {
+            execute_leg::<MeteoraDynamicAmmSwap>(program_id, leg_accounts, |ctx| {
+                arbitrage_bot::meteora_dynamic_amm_swap(ctx, amount_in, minimum_amount_out)
+            })?;
+        },
+        ArbitrageStep::Phoenix(amount_in, minimum_amount_out) => {
+            execute_leg::<PhoenixSwap>(program_id, leg_accounts, |ctx| {
+                arbitrage_bot::phoenix_swap(ctx, amount_in, minimum_amount_out)
+            })?;
+        },
+        ArbitrageStep::Lifinity(amount_in, minimum_amount_out) => {
+            execute_leg::<LifinitySwap>(program_id, leg_accounts, |ctx| {
+                arbitrage_bot::lifinity_swap(ctx, amount_in, minimum_amount_out)
+            })?;
+        },
+        ArbitrageStep::Jupiter(amount_in, minimum_amount_out, route) => {
+            execute_leg::<JupiterSwap>(program_id, leg_accounts, |ctx| {
+                arbitrage_bot::jupiter_swap(ctx, amount_in, minimum_amount_out, route)
+            })?;
+        },
+    }
+
+    // end_swap measured what this branch received
+    swap_state.reload()?;
+    slots[output_index].fill(swap_state.current_token, swap_state.current_account, swap_state.swap_input)
+}
+
+pub fn quote_legs<'info>(
+    program_id: &Pubkey,
+    input_token: Pubkey,
+    amount_in: u64,
+    mut remaining_accounts: &'info [AccountInfo<'info>],
+    route: Vec<RouteLeg>
+) -> Result<Vec<LegQuote>> {
+    let mut slots = route_slots(&route, input_token, Pubkey::default(), amount_in)?;
+    let mut quotes = Vec::with_capacity(route.len());
+
+    for (index, leg) in route.iter().enumerate() {
+        let quote = quote_route_leg(program_id, &mut slots, &mut remaining_accounts, leg)
+            .map_err(|err| leg_failed(index, err))?;
+        quotes.push(quote);
+    }
+    require!(remaining_accounts.is_empty(), ErrorCode::InvalidRoute);
+
+    final_slot(&slots, &route)?;
+    Ok(quotes)
+}
+
+fn quote_route_leg<'info>(
+    program_id: &Pubkey,
+    slots: &mut [RouteSlot],
+    remaining_accounts: &mut &'info [AccountInfo<'info>],
+    leg: &RouteLeg
+) -> Result<LegQuote> {
+    let leg_accounts = take_leg_accounts(remaining_accounts, leg)?;
+
+    let (input_index, output_index) = (leg.input_index as usize, leg.output_index as usize);
+    let amount_in = slots[input_index].draw(leg.percent)?;
+    let input_mint = slots[input_index].mint;
+
+    let (output_mint, amount_out) = match leg.step {
+        ArbitrageStep::Whirlpool(..) => quote_leg::<WhirlpoolSwap, _>(program_id, leg_accounts, |accounts, _| {
+            quote_whirlpool(accounts, input_mint, amount_in)
+        })?,
+        ArbitrageStep::Raydium(..) => quote_leg::<RaydiumSwap, _>(program_id, leg_accounts, |accounts, _| {
+            quote_raydium(accounts, input_mint, amount_in)
+        })?,
+        ArbitrageStep::MeteoraDlmm(..) => quote_leg::<MeteoraDlmmSwap, _>(program_id, leg_accounts, |accounts, bin_arrays| {
+            quote_meteora_dlmm(accounts, bin_arrays, input_mint, amount_in)
+        })?,
+        ArbitrageStep::MeteoraDynamicAmm(..) => quote_leg::<MeteoraDynamicAmmSwap, _>(program_id, leg_accounts, |accounts, _| {
+            quote_meteora_dynamic_amm(accounts, input_mint, amount_in)
+        })?,
+        _ => return err!(ErrorCode::UnsupportedQuote),
+    };
+
+    slots[output_index].fill(output_mint, Pubkey::default(), amount_out)?;
+    Ok(LegQuote { amount_in, amount_out })
+}
+
+// Deserializes a leg's accounts like `execute_leg` but only reads them:
+// nothing is written back, so the quote leaves every account untouched
+pub fn quote_leg<'info, T, R>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    quote: impl FnOnce(&T, &'info [AccountInfo<'info>]) -> Result<R>
+) -> Result<R>
+where
+    T: Accounts<'info, T::Bumps> + Bumps,
+    T::Bumps: Default,
+{
+    let mut leg_accounts = accounts;
+    let mut bumps = T::Bumps::default();
+    let leg = T::try_accounts(
+        program_id,
+        &mut leg_accounts,
+        &[],
+        &mut bumps,
+        &mut BTreeSet::new(),
+    )?;
+    quote(&leg, leg_accounts)
+}
+
+pub fn execute_leg<'info, T>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    handler: impl FnOnce(Context<'_, '_, 'info, 'info, T>) -> Result<()>
+) -> Result<()>
+where
+    T: Accounts<'info, T::Bumps> + AccountsExit<'info> + Bumps,
+    T::Bumps: Default,
+{
+    let mut leg_accounts = accounts;
+    let mut bumps = T::Bumps::default();
+    let mut leg = T::try_accounts(
+        program_id,
+        &mut leg_accounts,
+        &[],
+        &mut bumps,
+        &mut BTreeSet::new(),
+    )?;
+    handler(Context::new(program_id, &mut leg, leg_accounts, bumps))?;
+    // persist the swap state so the next leg deserializes the updated amount
+    leg.exit(program_id)
+}
+
+pub fn prepare_swap(
+    swap_state: &Account<SwapState>,
+    user_src: &InterfaceAccount<TokenAccount>,
+    instructions: &AccountInfo
+) -> Result<u64> {
+    require!(swap_state.is_active(Clock::get()?.slot), ErrorCode::StaleSwapState);
+    require_inside_swap(instructions, &swap_state.key())?;
+    require_keys_eq!(user_src.mint, swap_state.current_token, ErrorCode::MintMismatch);
+    // legs chain through the accounts they pay into, starting from `src`
+    require_keys_eq!(user_src.key(), swap_state.current_account, ErrorCode::InvalidLegAccount);
     let amount_in = swap_state.swap_input;
     msg!("swap amount in: {:?} for token: {:?}", amount_in, swap_state.current_token);
     Ok(amount_in)
@@ -205,28 +990,147 @@ pub fn prepare_swap(swap_state: &Account<SwapState>) -> Result<u64> {
 
 pub fn end_swap(
     swap_state: &mut Account<SwapState>,
-    user_dst: &mut Account<TokenAccount>
-) -> Result<()> {
+    user_dst: &mut InterfaceAccount<TokenAccount>,
+    dex: Dex,
+    pool: Pubkey,
+    fee_rate: u64,
+    minimum_amount_out: u64
+) -> Result<SwapLegExecuted> {
+    // measure what actually arrived rather than what the pool sent: Token-2022 transfer
+    // fees are withheld in the destination's extension data and never count toward `amount`
     let dst_start_balance = user_dst.amount;
     user_dst.reload()?;
     let dst_end_balance = user_dst.amount;
     let swap_amount_out = dst_end_balance.saturating_sub(dst_start_balance);
+    // the profit check only sees `src`, so the input token never moves through another account
+    if user_dst.mint == swap_state.input_token {
+        require_keys_eq!(user_dst.key(), swap_state.src, ErrorCode::InvalidSourceAccount);
+    }
     msg!(
         "swap amount out: {:?} for token: {:?}", 
         swap_amount_out, 
         swap_state.current_token
     );
+    // the pools check their own output before transfer fees, hold the leg to what arrived
+    require_gte!(swap_amount_out, minimum_amount_out, ErrorCode::InsufficientLegOutput);
+
+    // reported only, the pool already took its fee out of what arrived
+    let fee = fee_on(swap_state.swap_input, fee_rate);
+
+    let leg = SwapLegExecuted {
+        dex,
+        pool,
+        input_mint: swap_state.current_token,
+        output_mint: user_dst.mint,
+        amount_in: swap_state.swap_input,
+        amount_out: swap_amount_out,
+        fee,
+    };
     swap_state.swap_input = swap_amount_out;
-    Ok(())
+    swap_state.current_token = user_dst.mint;
+    swap_state.current_account = user_dst.key();
+    Ok(leg)
+}
+
+// `amount * fee_rate`, rounded up
+pub fn fee_on(amount: u64, fee_rate: u64) -> u64 {
+    let fee = (amount as u128 * fee_rate as u128).div_ceil(FEE_RATE_DENOMINATOR as u128);
+    fee.min(amount as u128) as u64
 }
 
-pub fn calculate_fees(step: &ArbitrageStep, amount: u64) -> u64 {
-    match step {
-        ArbitrageStep::Orca(_, _) => amount * 30 / 10000,  // 0.3% fee
-        ArbitrageStep::Raydium(_, _) => amount * 25 / 10000, // 0.25% fee
-        ArbitrageStep::Meteora(_, _) => amount * 20 / 10000, // 0.2% fee
-        ArbitrageStep::Phoenix(_, _) => amount * 15 / 10000, // 0.15% fee
-        ArbitrageStep::Lifinity(_, _) => amount * 35 / 10000, // 0.35% fee
-        ArbitrageStep::Jupiter(_, _) => amount * 10 / 10000, // 0.1% fee
+pub fn check_profit(
+    swap_state: &SwapState,
+    final_balance: u64,
+    threshold: &ProfitThreshold
+) -> Result<u64> {
+    // a flash loan sits in both balances, only its fee eats into the profit
+    let init_balance = swap_state.start_balance.saturating_add(swap_state.flash_fee);
+    let required_profit = threshold.required_profit(swap_state.start_input);
+    msg!(
+        "old = {:?}; new = {:?}; diff = {:?}; required = {:?}", 
+        init_balance, 
+        final_balance, 
+        final_balance.saturating_sub(init_balance), 
+        required_profit
+    );
+
+    // the pools took their fees out of the realized balance already
+    require!(final_balance > init_balance, ErrorCode::NoProfit);
+    let profit = final_balance - init_balance;
+    require!(profit >= required_profit, ErrorCode::ProfitBelowThreshold);
+    Ok(profit)
+}
+
+pub fn record_arbitrage(
+    arbitrage_state: &mut ArbitrageState,
+    swap_state: &SwapState,
+    profit: u64
+) {
+    arbitrage_state.record(swap_state.start_input, profit);
+    emit!(ArbitrageExecuted {
+        authority: swap_state.authority,
+        input_token: swap_state.input_token,
+        amount_in: swap_state.start_input,
+        amount_out: swap_state.swap_input,
+        profit,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_on_rounds_up() {
+        assert_eq!(fee_on(1_000_000, 2_500), 2_500);
+        assert_eq!(fee_on(1_001, 2_500), 3);
+        assert_eq!(fee_on(1, 1), 1);
+        assert_eq!(fee_on(0, 2_500), 0);
+        assert_eq!(fee_on(1_000, 0), 0);
+    }
+
+    #[test]
+    fn fee_on_never_exceeds_the_amount() {
+        assert_eq!(fee_on(u64::MAX, FEE_RATE_DENOMINATOR), u64::MAX);
+        assert_eq!(fee_on(1_000, u64::MAX), 1_000);
+    }
+
+    fn swap_state(start_balance: u64, start_input: u64, flash_fee: u64) -> SwapState {
+        SwapState { start_balance, start_input, flash_fee, ..SwapState::default() }
+    }
+
+    #[test]
+    fn check_profit_returns_the_gain_over_the_start_balance() {
+        let threshold = ProfitThreshold { min_profit: 10, min_profit_bps: 0 };
+        assert_eq!(check_profit(&swap_state(1_000, 500, 0), 1_010, &threshold).unwrap(), 10);
+        assert_eq!(
+            check_profit(&swap_state(1_000, 500, 0), 1_009, &threshold).unwrap_err(),
+            ErrorCode::ProfitBelowThreshold.into()
+        );
+        assert_eq!(
+            check_profit(&swap_state(1_000, 500, 0), 1_000, &threshold).unwrap_err(),
+            ErrorCode::NoProfit.into()
+        );
+    }
+
+    #[test]
+    fn check_profit_charges_the_flash_fee() {
+        let threshold = ProfitThreshold::default();
+        // the borrowed amount is in both balances, only the fee of 9 has to be earned back
+        assert_eq!(check_profit(&swap_state(101_000, 100_000, 9), 101_010, &threshold).unwrap(), 1);
+        assert_eq!(
+            check_profit(&swap_state(101_000, 100_000, 9), 101_009, &threshold).unwrap_err(),
+            ErrorCode::NoProfit.into()
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn check_profit_scales_the_threshold_with_the_swap_input() {
+        let threshold = ProfitThreshold { min_profit: 0, min_profit_bps: 100 };
+        assert_eq!(check_profit(&swap_state(1_000, 1_000, 0), 1_010, &threshold).unwrap(), 10);
+        assert_eq!(
+            check_profit(&swap_state(1_000, 2_000, 0), 1_010, &threshold).unwrap_err(),
+            ErrorCode::ProfitBelowThreshold.into()
+        );
+    }
+}
@@ -0,0 +1,133 @@
+// File: src/quotes/math.rs
+
+use anchor_lang::require;
+use crate::error::ErrorCode;
+
+pub use uints::{U256, U512};
+
+// construct_uint! expands to code clippy would have written differently
+#[allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+mod uints {
+    use uint::construct_uint;
+
+    construct_uint! {
+        pub struct U256(4);
+    }
+
+    // wide enough for a u128 liquidity shifted into Q64.64 and multiplied by a price
+    construct_uint! {
+        pub struct U512(8);
+    }
+}
+
+// sqrt(1.0001)^(2^i) in Q32.96 and 1/sqrt(1.0001)^(2^i) in Q64.64, both rounded
+// down, the tables the whirlpool program itself prices ticks with
+const POSITIVE_TICK_FACTORS: [u128; 19] = [
+    79232123823359799118286999567,
+    79236085330515764027303304731,
+    79244008939048815603706035061,
+    79259858533276714757314932305,
+    79291567232598584799939703904,
+    79355022692464371645785046466,
+    79482085999252804386437311141,
+    79736823300114093921829183326,
+    80248749790819932309965073892,
+    81282483887344747381513967011,
+    83390072131320151908154831281,
+    87770609709833776024991924138,
+    97234110755111693312479820773,
+    119332217159966728226237229890,
+    179736315981702064433883588727,
+    407748233172238350107850275304,
+    2098478828474011932436660412517,
+    55581415166113811149459800483533,
+    38992368544603139932233054999993551,
+];
+const NEGATIVE_TICK_FACTORS: [u128; 19] = [
+    18445821805675392311,
+    18444899583751176498,
+    18443055278223354162,
+    18439367220385604838,
+    18431993317065449817,
+    18417254355718160513,
+    18387811781193591352,
+    18329067761203520168,
+    18212142134806087854,
+    17980523815641551639,
+    17526086738831147013,
+    16651378430235024244,
+    15030750278693429944,
+    12247334978882834399,
+    8131365268884726200,
+    3584323654723342297,
+    696457651847595233,
+    26294789957452057,
+    37481735321082,
+];
+
+/// Q64.64 square root of 1.0001^tick, bit for bit as the whirlpool program computes it.
+pub fn sqrt_price_at_tick(tick: i32) -> u128 {
+    let abs_tick = tick.unsigned_abs();
+    if tick >= 0 {
+        let mut ratio = U256::from(if abs_tick & 1 != 0 { POSITIVE_TICK_FACTORS[0] } else { 1 << 96 });
+        for (bit, factor) in POSITIVE_TICK_FACTORS.iter().enumerate().skip(1) {
+            if abs_tick & (1 << bit) != 0 {
+                ratio = (ratio * U256::from(*factor)) >> 96;
+            }
+        }
+        (ratio >> 32).as_u128()
+    } else {
+        let mut ratio = if abs_tick & 1 != 0 { NEGATIVE_TICK_FACTORS[0] } else { 1 << 64 };
+        for (bit, factor) in NEGATIVE_TICK_FACTORS.iter().enumerate().skip(1) {
+            if abs_tick & (1 << bit) != 0 {
+                ratio = (ratio * factor) >> 64;
+            }
+        }
+        ratio
+    }
+}
+
+pub fn mul_div(a: u128, b: u128, denominator: u128, round_up: bool) -> anchor_lang::Result<u128> {
+    div(U512::from(a) * U512::from(b), U512::from(denominator), round_up)
+}
+
+pub fn div(numerator: U512, denominator: U512, round_up: bool) -> anchor_lang::Result<u128> {
+    // denominators come straight from pool state, an empty or broken pool must not abort
+    require!(!denominator.is_zero(), ErrorCode::QuoteDivisionByZero);
+    let (quotient, remainder) = numerator.div_mod(denominator);
+    let quotient = if round_up && !remainder.is_zero() { quotient + 1 } else { quotient };
+    // saturate so an unreachable target reads as "more than any u64 amount"
+    Ok(if quotient > U512::from(u128::MAX) { u128::MAX } else { quotient.as_u128() })
+}
+
+pub fn to_u64(amount: u128) -> u64 {
+    u64::try_from(amount).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swaps::{MAX_SQRT_PRICE_X64, MIN_SQRT_PRICE_X64};
+
+    #[test]
+    fn sqrt_price_at_tick_matches_whirlpool() {
+        assert_eq!(sqrt_price_at_tick(0), 1 << 64);
+        assert_eq!(sqrt_price_at_tick(1), 18447666387855959850);
+        assert_eq!(sqrt_price_at_tick(-1), 18445821805675392311);
+        assert_eq!(sqrt_price_at_tick(443636), MAX_SQRT_PRICE_X64);
+        assert_eq!(sqrt_price_at_tick(-443636), MIN_SQRT_PRICE_X64);
+    }
+
+    #[test]
+    fn mul_div_rounds_and_saturates() {
+        assert_eq!(mul_div(10, 10, 3, false).unwrap(), 33);
+        assert_eq!(mul_div(10, 10, 3, true).unwrap(), 34);
+        assert_eq!(mul_div(10, 9, 3, true).unwrap(), 30);
+        assert_eq!(mul_div(u128::MAX, 4, 2, false).unwrap(), u128::MAX);
+    }
+
+    #[test]
+    fn mul_div_rejects_zero_divisor() {
+        assert_eq!(mul_div(1, 1, 0, false).unwrap_err(), ErrorCode::QuoteDivisionByZero.into());
+    }
+}
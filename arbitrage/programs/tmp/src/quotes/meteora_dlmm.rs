@@ -0,0 +1,154 @@
+use anchor_lang::prelude::*;
+use dlmm::state::bin::BinArray;
+use crate::error::ErrorCode;
+use crate::quotes::math::{mul_div, to_u64};
+use crate::swaps::{bin_fee_rate, volatility_references, MeteoraDlmmSwap, DLMM_FEE_PRECISION};
+
+const MAX_BIN_PER_ARRAY: i32 = 70;
+
+/// Expected output of swapping `amount_in` of `input_mint` through the pair,
+/// draining bins from the active one outwards across the leg's bin arrays.
+pub fn quote_meteora_dlmm<'info>(
+    accounts: &MeteoraDlmmSwap<'info>,
+    bin_arrays: &'info [AccountInfo<'info>],
+    input_mint: Pubkey,
+    amount_in: u64,
+) -> Result<(Pubkey, u64)> {
+    let lb_pair = accounts.lb_pair.load()?;
+    let swap_for_y = input_mint == lb_pair.token_x_mint;
+    require!(swap_for_y || input_mint == lb_pair.token_y_mint, ErrorCode::MintMismatch);
+    let output_mint = if swap_for_y { lb_pair.token_y_mint } else { lb_pair.token_x_mint };
+
+    let mut loaded = Vec::with_capacity(bin_arrays.len());
+    for bin_array in bin_arrays {
+        let bin_array = AccountLoader::<BinArray>::try_from(bin_array)?;
+        let (index, lb_pair) = {
+            let bin_array = bin_array.load()?;
+            (bin_array.index, bin_array.lb_pair)
+        };
+        require_keys_eq!(lb_pair, accounts.lb_pair.key(), ErrorCode::InvalidMeteoraPool);
+        loaded.push((index, bin_array));
+    }
+
+    let references = volatility_references(&lb_pair, Clock::get()?.unix_timestamp);
+    let bin_range = (lb_pair.parameters.min_bin_id, lb_pair.parameters.max_bin_id);
+    let amount_out = swap_across_bins(lb_pair.active_id, bin_range, amount_in as u128, swap_for_y, |bin_id| {
+        let array_index = bin_id.div_euclid(MAX_BIN_PER_ARRAY) as i64;
+        let (_, bin_array) = loaded
+            .iter()
+            .find(|(index, _)| *index == array_index)
+            .ok_or(error!(ErrorCode::QuoteOutOfRange))?;
+        let bin_array = bin_array.load()?;
+        let bin = &bin_array.bins[bin_id.rem_euclid(MAX_BIN_PER_ARRAY) as usize];
+        Ok(BinLiquidity {
+            price: bin.price,
+            reserve_out: if swap_for_y { bin.amount_y } else { bin.amount_x } as u128,
+            fee_rate: bin_fee_rate(&lb_pair, references, bin_id),
+        })
+    })?;
+
+    Ok((output_mint, to_u64(amount_out)))
+}
+
+// What a swap sees of one bin
+struct BinLiquidity {
+    // Q64.64 price of x in y
+    price: u128,
+    // amount of the output token the bin holds
+    reserve_out: u128,
+    fee_rate: u128,
+}
+
+// Exact-input swap draining bins from `active_id` outwards, as the pair does
+fn swap_across_bins(
+    mut active_id: i32,
+    (min_bin_id, max_bin_id): (i32, i32),
+    amount_in: u128,
+    swap_for_y: bool,
+    bin_at: impl Fn(i32) -> Result<BinLiquidity>
+) -> Result<u128> {
+    let mut amount_left = amount_in;
+    let mut amount_out: u128 = 0;
+    while amount_left > 0 {
+        let bin = bin_at(active_id)?;
+        if bin.reserve_out > 0 {
+            let (bin_amount_in, bin_amount_out) = swap_in_bin(&bin, amount_left, swap_for_y)?;
+            amount_left -= bin_amount_in;
+            amount_out += bin_amount_out;
+        }
+
+        if amount_left > 0 {
+            active_id = if swap_for_y { active_id - 1 } else { active_id + 1 };
+            require!(active_id >= min_bin_id && active_id <= max_bin_id, ErrorCode::QuoteOutOfRange);
+        }
+    }
+    Ok(amount_out)
+}
+
+// Input the bin takes out of `amount_left`, fee included, and the output it gives for it
+fn swap_in_bin(bin: &BinLiquidity, amount_left: u128, swap_for_y: bool) -> Result<(u128, u128)> {
+    require!(bin.price > 0, ErrorCode::InvalidMeteoraPool);
+    let fee_rate = bin.fee_rate;
+
+    // input that empties the bin, grossed up by the fee on top of it
+    let max_amount_in = if swap_for_y {
+        mul_div(bin.reserve_out, 1 << 64, bin.price, true)?
+    } else {
+        mul_div(bin.reserve_out, bin.price, 1 << 64, true)?
+    };
+    let max_amount_in = max_amount_in + mul_div(max_amount_in, fee_rate, DLMM_FEE_PRECISION - fee_rate, true)?;
+    if amount_left > max_amount_in {
+        return Ok((max_amount_in, bin.reserve_out));
+    }
+
+    let fee = mul_div(amount_left, fee_rate, DLMM_FEE_PRECISION, true)?;
+    let amount_in_less_fee = amount_left - fee;
+    let amount_out = if swap_for_y {
+        mul_div(amount_in_less_fee, bin.price, 1 << 64, false)?
+    } else {
+        mul_div(amount_in_less_fee, 1 << 64, bin.price, false)?
+    };
+    Ok((amount_left, amount_out.min(bin.reserve_out)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1% in DLMM fee precision
+    const FEE_RATE: u128 = 10_000_000;
+
+    fn bin(price: u128, reserve_out: u128) -> BinLiquidity {
+        BinLiquidity { price, reserve_out, fee_rate: FEE_RATE }
+    }
+
+    #[test]
+    fn swap_in_bin_takes_fee_from_exact_input() {
+        assert_eq!(swap_in_bin(&bin(1 << 64, 10_000), 1_000, true).unwrap(), (1_000, 990));
+        // paying y at 2 y per x buys half as much x
+        assert_eq!(swap_in_bin(&bin(2 << 64, 10_000), 1_000, false).unwrap(), (1_000, 495));
+    }
+
+    #[test]
+    fn swap_in_bin_grosses_up_input_that_empties_the_bin() {
+        assert_eq!(swap_in_bin(&bin(1 << 64, 500), 1_000, true).unwrap(), (506, 500));
+    }
+
+    #[test]
+    fn swap_across_bins_walks_down_for_y() {
+        let bins = |bin_id| match bin_id {
+            0 => Ok(bin(1 << 64, 500)),
+            -1 => Ok(bin(1 << 63, 1_000)),
+            _ => Ok(bin(1 << 63, 0)),
+        };
+        assert_eq!(swap_across_bins(0, (-10, 10), 1_506, true, bins).unwrap(), 500 + 495);
+    }
+
+    #[test]
+    fn swap_across_bins_skips_empty_bins_and_stops_at_range() {
+        let bins = |bin_id| Ok(bin(1 << 64, if bin_id == 2 { 100 } else { 0 }));
+        assert_eq!(swap_across_bins(0, (-10, 10), 50, false, bins).unwrap(), 49);
+        let result = swap_across_bins(0, (-10, 3), 500, false, bins);
+        assert_eq!(result.unwrap_err(), ErrorCode::QuoteOutOfRange.into());
+    }
+}
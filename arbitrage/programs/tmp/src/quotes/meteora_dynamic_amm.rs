@@ -0,0 +1,86 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, TokenAccount};
+use dynamic_amm::state::CurveType;
+use crate::error::ErrorCode;
+use crate::quotes::math::{mul_div, to_u64};
+use crate::swaps::{MeteoraDynamicAmmSwap, METEORA_VAULT_PROGRAM_ID};
+
+const LOCKED_PROFIT_DEGRADATION_DENOMINATOR: u128 = 1_000_000_000_000;
+
+/// The fields of the Meteora vault `Vault` needed to value a pool's vault shares.
+pub struct MeteoraVault {
+    pub total_amount: u64,
+    pub lp_mint: Pubkey,
+    pub last_updated_locked_profit: u64,
+    pub last_report: u64,
+    pub locked_profit_degradation: u64,
+}
+
+impl MeteoraVault {
+    pub const LEN: usize = 1227;
+
+    pub fn load(vault: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(*vault.owner, METEORA_VAULT_PROGRAM_ID, ErrorCode::InvalidMeteoraPool);
+        let data = vault.try_borrow_data()?;
+        require!(data.len() >= Self::LEN, ErrorCode::InvalidMeteoraPool);
+
+        let pubkey_at = |offset: usize| Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap());
+        let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+
+        Ok(Self {
+            total_amount: u64_at(11),
+            lp_mint: pubkey_at(115),
+            last_updated_locked_profit: u64_at(1203),
+            last_report: u64_at(1211),
+            locked_profit_degradation: u64_at(1219),
+        })
+    }
+
+    // profit from strategies unlocks linearly and is not withdrawable before then
+    pub fn unlocked_amount(&self, now: u64) -> Result<u64> {
+        let locked_fund_ratio = now.saturating_sub(self.last_report) as u128 * self.locked_profit_degradation as u128;
+        let locked_profit = match LOCKED_PROFIT_DEGRADATION_DENOMINATOR.checked_sub(locked_fund_ratio) {
+            Some(ratio) => mul_div(self.last_updated_locked_profit as u128, ratio, LOCKED_PROFIT_DEGRADATION_DENOMINATOR, false)?,
+            None => 0,
+        };
+        Ok(self.total_amount.saturating_sub(to_u64(locked_profit)))
+    }
+}
+
+/// Expected output of a constant product swap against the token amounts the
+/// pool holds through its vault shares.
+pub fn quote_meteora_dynamic_amm(accounts: &MeteoraDynamicAmmSwap, input_mint: Pubkey, amount_in: u64) -> Result<(Pubkey, u64)> {
+    let pool = &accounts.pool;
+    require!(pool.curve_type == CurveType::ConstantProduct, ErrorCode::UnsupportedQuote);
+    let a_to_b = input_mint == pool.token_a_mint;
+    require!(a_to_b || input_mint == pool.token_b_mint, ErrorCode::MintMismatch);
+    let output_mint = if a_to_b { pool.token_b_mint } else { pool.token_a_mint };
+
+    let now = Clock::get()?.unix_timestamp as u64;
+    let token_a_amount = pool_token_amount(&accounts.a_vault, &accounts.a_vault_lp, &accounts.a_vault_lp_mint, now)?;
+    let token_b_amount = pool_token_amount(&accounts.b_vault, &accounts.b_vault_lp, &accounts.b_vault_lp_mint, now)?;
+    let (reserve_in, reserve_out) = if a_to_b {
+        (token_a_amount, token_b_amount)
+    } else {
+        (token_b_amount, token_a_amount)
+    };
+
+    let fees = &pool.fees;
+    let trade_fee = mul_div(amount_in as u128, fees.trade_fee_numerator as u128, fees.trade_fee_denominator as u128, false)?;
+    let amount_in_less_fee = (amount_in as u128).saturating_sub(trade_fee);
+    let amount_out = mul_div(reserve_out as u128, amount_in_less_fee, reserve_in as u128 + amount_in_less_fee, false)?;
+
+    Ok((output_mint, to_u64(amount_out)))
+}
+
+// the pool's share of the vault's unlocked amount
+fn pool_token_amount(vault: &AccountInfo, vault_lp: &AccountInfo, vault_lp_mint: &AccountInfo, now: u64) -> Result<u64> {
+    let vault = MeteoraVault::load(vault)?;
+    require_keys_eq!(vault_lp_mint.key(), vault.lp_mint, ErrorCode::InvalidMeteoraPool);
+    let lp_supply = Mint::try_deserialize(&mut &vault_lp_mint.try_borrow_data()?[..])?.supply;
+    let pool_lp = TokenAccount::try_deserialize(&mut &vault_lp.try_borrow_data()?[..])?.amount;
+    if lp_supply == 0 {
+        return Ok(0);
+    }
+    Ok(to_u64(mul_div(vault.unlocked_amount(now)? as u128, pool_lp as u128, lp_supply as u128, false)?))
+}
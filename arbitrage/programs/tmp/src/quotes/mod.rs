@@ -0,0 +1,21 @@
+// File: program/src/quotes/mod.rs
+
+pub mod math;
+pub mod whirlpool;
+pub mod raydium;
+pub mod meteora_dlmm;
+pub mod meteora_dynamic_amm;
+
+pub use whirlpool::*;
+pub use raydium::*;
+pub use meteora_dlmm::*;
+pub use meteora_dynamic_amm::*;
+
+use anchor_lang::prelude::*;
+
+/// Expected amounts of one leg of a quoted route, in route order.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq)]
+pub struct LegQuote {
+    pub amount_in: u64,
+    pub amount_out: u64,
+}
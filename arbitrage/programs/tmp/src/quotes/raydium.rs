@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use crate::error::ErrorCode;
+use crate::quotes::math::{mul_div, to_u64};
+use crate::swaps::{check_raydium_pool, RaydiumSwap};
+
+// serum `OpenOrders` offsets of native_coin_total and native_pc_total,
+// behind the 5 byte "serum" padding
+const OPEN_ORDERS_COIN_TOTAL: usize = 85;
+const OPEN_ORDERS_PC_TOTAL: usize = 101;
+
+/// Expected output of `swap_base_in` against the AMM reserves: the vaults plus what
+/// sits on the order book, less the pnl the AMM has yet to take.
+pub fn quote_raydium(accounts: &RaydiumSwap, input_mint: Pubkey, amount_in: u64) -> Result<(Pubkey, u64)> {
+    let info = check_raydium_pool(accounts)?;
+    let coin_to_pc = input_mint == info.coin_mint;
+    require!(coin_to_pc || input_mint == info.pc_mint, ErrorCode::MintMismatch);
+    let output_mint = if coin_to_pc { info.pc_mint } else { info.coin_mint };
+
+    let (coin_on_book, pc_on_book) = open_orders_totals(&accounts.amm_open_orders)?;
+    let coin_reserve = token_amount(&accounts.pool_coin_token_account)?
+        .saturating_add(coin_on_book)
+        .saturating_sub(info.need_take_pnl_coin);
+    let pc_reserve = token_amount(&accounts.pool_pc_token_account)?
+        .saturating_add(pc_on_book)
+        .saturating_sub(info.need_take_pnl_pc);
+    let (reserve_in, reserve_out) = if coin_to_pc {
+        (coin_reserve, pc_reserve)
+    } else {
+        (pc_reserve, coin_reserve)
+    };
+
+    let swap_fee = mul_div(amount_in as u128, info.swap_fee_numerator as u128, info.swap_fee_denominator as u128, true)?;
+    let amount_in_less_fee = (amount_in as u128).saturating_sub(swap_fee);
+    let amount_out = mul_div(reserve_out as u128, amount_in_less_fee, reserve_in as u128 + amount_in_less_fee, false)?;
+
+    Ok((output_mint, to_u64(amount_out)))
+}
+
+fn token_amount(token_account: &AccountInfo) -> Result<u64> {
+    let data = token_account.try_borrow_data()?;
+    Ok(TokenAccount::try_deserialize(&mut &data[..])?.amount)
+}
+
+fn open_orders_totals(open_orders: &AccountInfo) -> Result<(u64, u64)> {
+    let data = open_orders.try_borrow_data()?;
+    if data.len() < OPEN_ORDERS_PC_TOTAL + 8 || &data[..5] != b"serum" {
+        // pools without an order book keep everything in the vaults
+        return Ok((0, 0));
+    }
+    let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    Ok((u64_at(OPEN_ORDERS_COIN_TOTAL), u64_at(OPEN_ORDERS_PC_TOTAL)))
+}
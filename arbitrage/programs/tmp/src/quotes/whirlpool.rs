@@ -0,0 +1,254 @@
+use anchor_lang::prelude::*;
+use crate::error::ErrorCode;
+use crate::quotes::math::{div, mul_div, sqrt_price_at_tick, to_u64, U512};
+use crate::swaps::{WhirlpoolSwap, FEE_RATE_DENOMINATOR, MAX_SQRT_PRICE_X64, MIN_SQRT_PRICE_X64};
+
+const TICK_ARRAY_SIZE: i32 = 88;
+const TICK_LEN: usize = 113;
+const MIN_TICK_INDEX: i32 = -443636;
+const MAX_TICK_INDEX: i32 = 443636;
+
+/// The initialized ticks of a whirlpool `TickArray`.
+pub struct WhirlpoolTickArray {
+    pub start_tick_index: i32,
+    pub whirlpool: Pubkey,
+    // (tick index, liquidity_net) of every initialized tick
+    pub ticks: Vec<(i32, i128)>,
+}
+
+impl WhirlpoolTickArray {
+    pub const LEN: usize = 9988;
+
+    pub fn load(tick_array: &AccountInfo, tick_spacing: u16) -> Result<Self> {
+        require_keys_eq!(*tick_array.owner, whirlpool_cpi::ID, ErrorCode::InvalidOrcaPool);
+        let data = tick_array.try_borrow_data()?;
+        require!(data.len() == Self::LEN, ErrorCode::InvalidOrcaPool);
+
+        // read byte-wise, the packed ticks are not aligned
+        let start_tick_index = i32::from_le_bytes(data[8..12].try_into().unwrap());
+        let ticks = (0..TICK_ARRAY_SIZE)
+            .filter_map(|i| {
+                let offset = 12 + i as usize * TICK_LEN;
+                let initialized = data[offset] != 0;
+                let liquidity_net = i128::from_le_bytes(data[offset + 1..offset + 17].try_into().unwrap());
+                initialized.then(|| (start_tick_index + i * tick_spacing as i32, liquidity_net))
+            })
+            .collect();
+        Ok(Self {
+            start_tick_index,
+            whirlpool: Pubkey::new_from_array(data[9956..9988].try_into().unwrap()),
+            ticks,
+        })
+    }
+}
+
+/// Expected output of swapping `amount_in` of `input_mint` through the whirlpool,
+/// walking the initialized ticks of the leg's three tick arrays.
+pub fn quote_whirlpool(accounts: &WhirlpoolSwap, input_mint: Pubkey, amount_in: u64) -> Result<(Pubkey, u64)> {
+    let whirlpool = &accounts.whirlpool;
+    let a_to_b = input_mint == whirlpool.token_mint_a;
+    require!(a_to_b || input_mint == whirlpool.token_mint_b, ErrorCode::MintMismatch);
+    let output_mint = if a_to_b { whirlpool.token_mint_b } else { whirlpool.token_mint_a };
+
+    let tick_spacing = whirlpool.tick_spacing;
+    let mut ticks = Vec::new();
+    let (mut lowest_tick, mut highest_tick) = (i32::MAX, i32::MIN);
+    for tick_array in [&accounts.tick_array_0, &accounts.tick_array_1, &accounts.tick_array_2] {
+        let tick_array = WhirlpoolTickArray::load(tick_array, tick_spacing)?;
+        require_keys_eq!(tick_array.whirlpool, whirlpool.key(), ErrorCode::InvalidOrcaPool);
+        lowest_tick = lowest_tick.min(tick_array.start_tick_index);
+        highest_tick = highest_tick.max(tick_array.start_tick_index + TICK_ARRAY_SIZE * tick_spacing as i32);
+        ticks.extend(tick_array.ticks);
+    }
+    ticks.sort_unstable();
+    ticks.dedup();
+    let lowest_tick = lowest_tick.max(MIN_TICK_INDEX);
+    let highest_tick = highest_tick.min(MAX_TICK_INDEX);
+
+    let curve = WhirlpoolCurve {
+        fee_rate: whirlpool.fee_rate as u128,
+        liquidity: whirlpool.liquidity,
+        sqrt_price: whirlpool.sqrt_price,
+        tick_current: whirlpool.tick_current_index,
+    };
+    let amount_out = swap_across_ticks(curve, &ticks, (lowest_tick, highest_tick), amount_in as u128, a_to_b)?;
+
+    Ok((output_mint, to_u64(amount_out)))
+}
+
+// The price state of the whirlpool a quote starts from
+struct WhirlpoolCurve {
+    fee_rate: u128,
+    liquidity: u128,
+    sqrt_price: u128,
+    tick_current: i32,
+}
+
+// Exact-input swap across the sorted initialized `ticks`, failing rather than
+// guessing once the price runs past the ticks the tick arrays cover
+fn swap_across_ticks(
+    curve: WhirlpoolCurve,
+    ticks: &[(i32, i128)],
+    (lowest_tick, highest_tick): (i32, i32),
+    amount_in: u128,
+    a_to_b: bool
+) -> Result<u128> {
+    let WhirlpoolCurve { fee_rate, mut liquidity, mut sqrt_price, mut tick_current } = curve;
+    let mut amount_remaining = amount_in;
+    let mut amount_out: u128 = 0;
+
+    while amount_remaining > 0 {
+        // the next initialized tick in the swap direction, or the edge of the supplied arrays
+        let next_tick = if a_to_b {
+            ticks.iter().rev().find(|(index, _)| *index <= tick_current)
+        } else {
+            ticks.iter().find(|(index, _)| *index > tick_current)
+        };
+        let (target_tick, liquidity_net) = match next_tick {
+            Some(&(index, liquidity_net)) => (index, Some(liquidity_net)),
+            None if a_to_b => (lowest_tick, None),
+            None => (highest_tick, None),
+        };
+        let target_price = sqrt_price_at_tick(target_tick).clamp(MIN_SQRT_PRICE_X64, MAX_SQRT_PRICE_X64);
+
+        let step = swap_step(amount_remaining, fee_rate, liquidity, sqrt_price, target_price, a_to_b)?;
+        amount_remaining -= step.amount_in + step.fee;
+        amount_out += step.amount_out;
+        sqrt_price = step.next_sqrt_price;
+
+        if sqrt_price == target_price && amount_remaining > 0 {
+            let liquidity_net = liquidity_net.ok_or(error!(ErrorCode::QuoteOutOfRange))?;
+            let liquidity_net = if a_to_b { -liquidity_net } else { liquidity_net };
+            liquidity = liquidity.checked_add_signed(liquidity_net).ok_or(error!(ErrorCode::QuoteOutOfRange))?;
+            tick_current = if a_to_b { target_tick - 1 } else { target_tick };
+        }
+    }
+
+    Ok(amount_out)
+}
+
+struct SwapStep {
+    amount_in: u128,
+    amount_out: u128,
+    fee: u128,
+    next_sqrt_price: u128,
+}
+
+// An exact-input step of the whirlpool swap towards `target_price`,
+// following the program's rounding so quotes do not overshoot the real swap
+fn swap_step(amount_remaining: u128, fee_rate: u128, liquidity: u128, sqrt_price: u128, target_price: u128, a_to_b: bool) -> Result<SwapStep> {
+    // whirlpool fee rates share the program's fee precision
+    let fee_denominator = FEE_RATE_DENOMINATOR as u128;
+    let amount_less_fee = mul_div(amount_remaining, fee_denominator - fee_rate, fee_denominator, false)?;
+    let amount_to_target = input_delta(sqrt_price, target_price, liquidity, a_to_b)?;
+
+    let next_sqrt_price = if amount_to_target <= amount_less_fee {
+        target_price
+    } else {
+        next_sqrt_price_from_input(sqrt_price, liquidity, amount_less_fee, a_to_b)?
+    };
+    let is_max_swap = next_sqrt_price == target_price;
+
+    let amount_in = if is_max_swap {
+        amount_to_target
+    } else {
+        input_delta(sqrt_price, next_sqrt_price, liquidity, a_to_b)?
+    };
+    let amount_out = if a_to_b {
+        delta_b(sqrt_price, next_sqrt_price, liquidity, false)?
+    } else {
+        delta_a(sqrt_price, next_sqrt_price, liquidity, false)?
+    };
+    let fee = if is_max_swap {
+        mul_div(amount_in, fee_rate, fee_denominator - fee_rate, true)?
+    } else {
+        amount_remaining - amount_in
+    };
+
+    Ok(SwapStep { amount_in, amount_out, fee, next_sqrt_price })
+}
+
+fn input_delta(sqrt_price: u128, target_price: u128, liquidity: u128, a_to_b: bool) -> Result<u128> {
+    if a_to_b {
+        delta_a(sqrt_price, target_price, liquidity, true)
+    } else {
+        delta_b(sqrt_price, target_price, liquidity, true)
+    }
+}
+
+// liquidity * (upper - lower) / (upper * lower)
+fn delta_a(price_0: u128, price_1: u128, liquidity: u128, round_up: bool) -> Result<u128> {
+    let (lower, upper) = (price_0.min(price_1), price_0.max(price_1));
+    let numerator = (U512::from(liquidity) << 64) * U512::from(upper - lower);
+    div(numerator, U512::from(upper) * U512::from(lower), round_up)
+}
+
+// liquidity * (upper - lower)
+fn delta_b(price_0: u128, price_1: u128, liquidity: u128, round_up: bool) -> Result<u128> {
+    let (lower, upper) = (price_0.min(price_1), price_0.max(price_1));
+    mul_div(liquidity, upper - lower, 1 << 64, round_up)
+}
+
+fn next_sqrt_price_from_input(sqrt_price: u128, liquidity: u128, amount: u128, a_to_b: bool) -> Result<u128> {
+    if a_to_b {
+        // selling a pushes the price down, rounded up to stay on the pool's side
+        let liquidity_shifted = U512::from(liquidity) << 64;
+        let denominator = liquidity_shifted + U512::from(amount) * U512::from(sqrt_price);
+        div(liquidity_shifted * U512::from(sqrt_price), denominator, true)
+    } else {
+        Ok(sqrt_price.saturating_add(mul_div(amount, 1 << 64, liquidity, false)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIQUIDITY: u128 = 1_000_000_000_000;
+
+    #[test]
+    fn swap_step_takes_fee_from_exact_input() {
+        // 0.3% pool at price 1, well short of the next tick
+        let step = swap_step(1_000_000, 3000, LIQUIDITY, 1 << 64, sqrt_price_at_tick(64), false).unwrap();
+        assert_eq!(step.amount_in, 997_000);
+        assert_eq!(step.fee, 3_000);
+        assert_eq!(step.amount_out, 996_999);
+        assert_eq!(step.next_sqrt_price, 18446762465113393104);
+    }
+
+    #[test]
+    fn swap_step_stops_at_target_price() {
+        let target = sqrt_price_at_tick(-64);
+        let step = swap_step(1_000_000_000_000, 3000, LIQUIDITY, 1 << 64, target, true).unwrap();
+        assert_eq!(step.next_sqrt_price, target);
+        assert_eq!(step.amount_in, 3_204_964_964);
+        assert_eq!(step.fee, 9_643_827);
+        assert_eq!(step.amount_out, 3_194_725_978);
+    }
+
+    #[test]
+    fn deltas_round_towards_the_pool() {
+        let (lower, upper) = (sqrt_price_at_tick(0), sqrt_price_at_tick(64));
+        assert_eq!(delta_a(lower, upper, LIQUIDITY, true), Ok(delta_a(lower, upper, LIQUIDITY, false).unwrap() + 1));
+        assert_eq!(delta_b(lower, upper, LIQUIDITY, true), Ok(delta_b(lower, upper, LIQUIDITY, false).unwrap() + 1));
+    }
+
+    fn curve() -> WhirlpoolCurve {
+        WhirlpoolCurve { fee_rate: 3000, liquidity: LIQUIDITY, sqrt_price: 1 << 64, tick_current: 0 }
+    }
+
+    #[test]
+    fn swap_across_ticks_crosses_initialized_tick() {
+        // liquidity doubles past tick 64
+        let ticks = [(64, LIQUIDITY as i128)];
+        let amount_out = swap_across_ticks(curve(), &ticks, (-128, 128), 5_000_000_000, false).unwrap();
+        assert_eq!(amount_out, 3_194_725_978 + 1_767_112_018);
+    }
+
+    #[test]
+    fn swap_across_ticks_stops_at_supplied_range() {
+        let ticks = [(64, LIQUIDITY as i128)];
+        let result = swap_across_ticks(curve(), &ticks, (-128, 128), 50_000_000_000, false);
+        assert_eq!(result.unwrap_err(), ErrorCode::QuoteOutOfRange.into());
+    }
+}
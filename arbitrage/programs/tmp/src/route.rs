@@ -0,0 +1,205 @@
+// File: src/route.rs
+
+use anchor_lang::prelude::*;
+use crate::error::ErrorCode;
+use crate::ix_data::RouteLeg;
+
+// bounded by the accounts a transaction can carry long before compute runs out
+pub const MAX_ROUTE_LEGS: usize = 16;
+
+// Amount of one token held between legs of a split route, addressed by
+// `RouteLeg::input_index` / `RouteLeg::output_index`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RouteSlot {
+    pub mint: Pubkey,
+    // token account holding `amount`, see `SwapState::current_account`
+    pub account: Pubkey,
+    pub amount: u64,
+    // amount the running split draws its percentages from
+    split_base: u64,
+    split_percent: u8,
+}
+
+impl RouteSlot {
+    pub fn is_settled(&self) -> bool {
+        self.split_percent == 0
+    }
+
+    // Takes `percent` of the slot for one branch. The branch that completes
+    // the split takes whatever is left so rounding never strands dust.
+    pub fn draw(&mut self, percent: u8) -> Result<u64> {
+        require!(percent > 0 && percent <= 100, ErrorCode::InvalidSplit);
+        if self.is_settled() {
+            self.split_base = self.amount;
+        }
+        let drawn = self.split_percent as u16 + percent as u16;
+        require!(drawn <= 100, ErrorCode::InvalidSplit);
+
+        let amount = if drawn == 100 {
+            self.split_percent = 0;
+            self.amount
+        } else {
+            self.split_percent = drawn as u8;
+            (self.split_base as u128 * percent as u128 / 100) as u64
+        };
+        self.amount -= amount;
+        Ok(amount)
+    }
+
+    // Merges a branch output into the slot, branches can only merge in the same account
+    pub fn fill(&mut self, mint: Pubkey, account: Pubkey, amount: u64) -> Result<()> {
+        require!(self.is_settled(), ErrorCode::InvalidSplit);
+        if self.amount == 0 {
+            self.mint = mint;
+            self.account = account;
+        }
+        require_keys_eq!(self.mint, mint, ErrorCode::MintMismatch);
+        require_keys_eq!(self.account, account, ErrorCode::InvalidLegAccount);
+        self.amount = self.amount.saturating_add(amount);
+        Ok(())
+    }
+}
+
+pub fn route_slots(route: &[RouteLeg], mint: Pubkey, account: Pubkey, amount: u64) -> Result<Vec<RouteSlot>> {
+    require!(!route.is_empty(), ErrorCode::InvalidRouteShape);
+    require_gte!(MAX_ROUTE_LEGS, route.len(), ErrorCode::RouteTooLong);
+    let len = route
+        .iter()
+        .map(|leg| leg.input_index.max(leg.output_index) as usize + 1)
+        .max()
+        .unwrap_or(1);
+    let mut slots = vec![RouteSlot::default(); len];
+    slots[0].mint = mint;
+    slots[0].account = account;
+    slots[0].amount = amount;
+    Ok(slots)
+}
+
+// Every branch has to merge back into the slot the last leg writes to
+pub fn final_slot(slots: &[RouteSlot], route: &[RouteLeg]) -> Result<RouteSlot> {
+    let final_index = route.last().map_or(0, |leg| leg.output_index as usize);
+    for (index, slot) in slots.iter().enumerate() {
+        require!(slot.is_settled(), ErrorCode::InvalidRouteShape);
+        require!(index == final_index || slot.amount == 0, ErrorCode::InvalidRouteShape);
+    }
+    Ok(slots[final_index])
+}
+
+// Splits the accounts of the next leg off the front of the remaining accounts
+pub fn take_leg_accounts<'info>(
+    remaining_accounts: &mut &'info [AccountInfo<'info>],
+    leg: &RouteLeg
+) -> Result<&'info [AccountInfo<'info>]> {
+    let leg_len = leg.accounts_len as usize;
+    require!(leg_len <= remaining_accounts.len(), ErrorCode::InvalidRoute);
+    let (leg_accounts, rest) = remaining_accounts.split_at(leg_len);
+    *remaining_accounts = rest;
+    Ok(leg_accounts)
+}
+
+// Logs which leg of the route failed before passing the error on
+pub fn leg_failed(index: usize, err: Error) -> Error {
+    msg!("route leg {} failed: {}", index, err);
+    err
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ArbitrageStep;
+
+    fn leg(percent: u8, input_index: u8, output_index: u8) -> RouteLeg {
+        RouteLeg {
+            step: ArbitrageStep::Raydium(0, 0),
+            accounts_len: 0,
+            percent,
+            input_index,
+            output_index,
+        }
+    }
+
+    // Draws every leg in order and fills its output account with `rate` times its input
+    fn run(slots: &mut [RouteSlot], route: &[RouteLeg], mint: Pubkey, account: Pubkey, rate: u64) -> Result<Vec<u64>> {
+        let mut drawn = Vec::new();
+        for leg in route {
+            let amount = slots[leg.input_index as usize].draw(leg.percent)?;
+            slots[leg.output_index as usize].fill(mint, account, amount * rate)?;
+            drawn.push(amount);
+        }
+        Ok(drawn)
+    }
+
+    #[test]
+    fn split_60_40_merges_back() {
+        let (input, output) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let route = [leg(60, 0, 1), leg(40, 0, 1)];
+        let mut slots = route_slots(&route, input, Pubkey::new_unique(), 1_001).unwrap();
+        // the branch completing the split takes the rounding dust
+        assert_eq!(run(&mut slots, &route, output, Pubkey::new_unique(), 2).unwrap(), vec![600, 401]);
+
+        let slot = final_slot(&slots, &route).unwrap();
+        assert_eq!((slot.mint, slot.amount), (output, 2_002));
+    }
+
+    #[test]
+    fn split_30_30_40_merges_back() {
+        let (input, output) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let route = [leg(30, 0, 1), leg(30, 0, 1), leg(40, 0, 1)];
+        let mut slots = route_slots(&route, input, Pubkey::new_unique(), 999).unwrap();
+        assert_eq!(run(&mut slots, &route, output, Pubkey::new_unique(), 1).unwrap(), vec![299, 299, 401]);
+
+        let slot = final_slot(&slots, &route).unwrap();
+        assert_eq!(slot.amount, 999);
+        assert_eq!(slots[0].amount, 0);
+    }
+
+    #[test]
+    fn draws_above_100_percent_are_rejected() {
+        let mut slots = route_slots(&[leg(60, 0, 1)], Pubkey::new_unique(), Pubkey::new_unique(), 1_000).unwrap();
+        slots[0].draw(60).unwrap();
+        assert_eq!(slots[0].draw(50).unwrap_err(), ErrorCode::InvalidSplit.into());
+        assert_eq!(slots[0].draw(0).unwrap_err(), ErrorCode::InvalidSplit.into());
+        assert_eq!(slots[0].draw(101).unwrap_err(), ErrorCode::InvalidSplit.into());
+    }
+
+    #[test]
+    fn fill_into_slot_mid_split_is_rejected() {
+        let mint = Pubkey::new_unique();
+        let mut slots = route_slots(&[leg(60, 0, 1)], mint, Pubkey::new_unique(), 1_000).unwrap();
+        slots[0].draw(60).unwrap();
+        let account = slots[0].account;
+        assert_eq!(slots[0].fill(mint, account, 10).unwrap_err(), ErrorCode::InvalidSplit.into());
+    }
+
+    #[test]
+    fn unmerged_branch_is_rejected() {
+        let (input, output) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // the first branch ends in slot 1, the route in slot 2
+        let route = [leg(50, 0, 1), leg(50, 0, 2)];
+        let mut slots = route_slots(&route, input, Pubkey::new_unique(), 1_000).unwrap();
+        run(&mut slots, &route, output, Pubkey::new_unique(), 1).unwrap();
+        assert_eq!(final_slot(&slots, &route).unwrap_err(), ErrorCode::InvalidRouteShape.into());
+    }
+
+    #[test]
+    fn unfinished_split_is_rejected() {
+        let (input, output) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let route = [leg(60, 0, 1)];
+        let mut slots = route_slots(&route, input, Pubkey::new_unique(), 1_000).unwrap();
+        run(&mut slots, &route, output, Pubkey::new_unique(), 1).unwrap();
+        assert_eq!(final_slot(&slots, &route).unwrap_err(), ErrorCode::InvalidRouteShape.into());
+    }
+
+    #[test]
+    fn branches_merging_into_another_account_are_rejected() {
+        let (input, output) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut slots = route_slots(&[leg(50, 0, 1), leg(50, 0, 1)], input, Pubkey::new_unique(), 1_000).unwrap();
+        let amount = slots[0].draw(50).unwrap();
+        slots[1].fill(output, Pubkey::new_unique(), amount).unwrap();
+        let amount = slots[0].draw(50).unwrap();
+        assert_eq!(
+            slots[1].fill(output, Pubkey::new_unique(), amount).unwrap_err(),
+            ErrorCode::InvalidLegAccount.into()
+        );
+    }
+}
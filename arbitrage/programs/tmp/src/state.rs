@@ -1,45 +1,156 @@
 // File: program/src/state.rs
 
 use anchor_lang::prelude::*;
+use crate::ix_data::ProfitThreshold;
 
 #[account]
 #[derive(Default)]
 pub struct ArbitrageState {
     pub authority: Pubkey,
+    pub input_token: Pubkey,
+    pub bump: u8,
     pub total_profit: u64,
     pub total_trades: u64,
+    pub total_volume: u64,
+}
+
+impl ArbitrageState {
+    pub const LEN: usize = 32 + 32 + 1 + 8 + 8 + 8;
+
+    pub fn record(&mut self, volume: u64, profit: u64) {
+        self.total_profit = self.total_profit.saturating_add(profit);
+        self.total_trades = self.total_trades.saturating_add(1);
+        self.total_volume = self.total_volume.saturating_add(volume);
+    }
 }
 
 #[account]
 #[derive(Default)]
 pub struct SwapState {
+    pub authority: Pubkey,
+    pub bump: u8,
     pub start_balance: u64,
+    pub start_input: u64,
     pub swap_input: u64,
     pub is_valid: bool,
     pub input_token: Pubkey,
     pub current_token: Pubkey,
+    // fee owed on an outstanding flash loan, deducted from the profit
+    pub flash_fee: u64,
+    // treasury owning the traded token accounts, default when the authority holds them
+    pub treasury: Pubkey,
+    // slot of the last `start_swap`, legs only run inside it
+    pub start_slot: u64,
+    // the `src` account of the last `start_swap`, legs trading the input token go through it
+    pub src: Pubkey,
+    // account holding `swap_input`, the next leg has to trade out of it
+    pub current_account: Pubkey,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub struct RoutePlan {
-    pub steps: Vec<ArbitrageStep>,
-    pub input_token: Pubkey,
-    pub output_token: Pubkey,
-    pub minimum_output_amount: u64,
+impl SwapState {
+    pub const LEN: usize = 32 + 1 + 8 + 8 + 8 + 1 + 32 + 32 + 8 + 32 + 8 + 32 + 32;
+
+    pub fn start(&mut self, src: Pubkey, start_balance: u64, swap_input: u64, slot: u64) {
+        self.start_balance = start_balance;
+        self.start_input = swap_input;
+        self.swap_input = swap_input;
+        self.current_token = self.input_token;
+        self.start_slot = slot;
+        self.src = src;
+        self.current_account = src;
+        self.is_valid = true;
+    }
+
+    // a swap left open by a transaction that never reached `profit_or_revert`
+    // stays valid, but goes stale once the slot it started in has passed
+    pub fn is_active(&self, slot: u64) -> bool {
+        self.is_valid && self.start_slot == slot
+    }
+
+    // whether `key` may own the token accounts this swap state trades from, only
+    // the treasury once one is set so the authority's own accounts stay out of reach
+    pub fn is_custodian(&self, key: &Pubkey) -> bool {
+        if self.treasury == Pubkey::default() {
+            *key == self.authority
+        } else {
+            *key == self.treasury
+        }
+    }
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub enum ArbitrageStep {
-    Orca(u64),
-    Raydium(u64),
-    Meteora(u64),
-    Jupiter(u64),
+#[account]
+#[derive(Default)]
+pub struct FlashVault {
+    pub mint: Pubkey,
+    pub vault_tokens: Pubkey,
+    pub bump: u8,
+    pub fee_bps: u16,
+    pub borrowed: u64,
+    pub total_fees: u64,
+    // swap state the outstanding loan's fee is charged to
+    pub borrower: Pubkey,
 }
 
-impl ArbitrageStep {
-    pub fn get_output_token(&self) -> Pubkey {
-        // Implement logic to return the output token for each DEX
-        // This is a placeholder and should be replaced with actual implementation
-        Pubkey::default()
+impl FlashVault {
+    pub const LEN: usize = 32 + 32 + 1 + 2 + 8 + 8 + 32;
+
+    // rounded up so a loan is never free
+    pub fn fee(&self, amount: u64) -> u64 {
+        let fee = (amount as u128 * self.fee_bps as u128).div_ceil(10_000);
+        u64::try_from(fee).unwrap_or(u64::MAX)
     }
-}
\ No newline at end of file
+}
+
+#[account]
+#[derive(Default)]
+pub struct Treasury {
+    pub owner: Pubkey,
+    pub operator: Pubkey,
+    pub bump: u8,
+}
+
+impl Treasury {
+    pub const LEN: usize = 32 + 32 + 1;
+}
+
+#[account]
+#[derive(Default)]
+pub struct Config {
+    pub admin: Pubkey,
+    pub paused: bool,
+    pub default_threshold: ProfitThreshold,
+    pub bump: u8,
+}
+
+impl Config {
+    pub const LEN: usize = 32 + 1 + (8 + 2) + 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault(fee_bps: u16) -> FlashVault {
+        FlashVault { fee_bps, ..FlashVault::default() }
+    }
+
+    #[test]
+    fn flash_fee_rounds_up() {
+        assert_eq!(vault(9).fee(1_000_000), 900);
+        assert_eq!(vault(9).fee(1_001), 1);
+        assert_eq!(vault(1).fee(1), 1);
+    }
+
+    #[test]
+    fn flash_fee_is_zero_without_a_rate_or_amount() {
+        assert_eq!(vault(0).fee(u64::MAX), 0);
+        assert_eq!(vault(9).fee(0), 0);
+    }
+
+    #[test]
+    fn flash_fee_at_the_cap_is_the_whole_amount() {
+        assert_eq!(vault(10_000).fee(u64::MAX), u64::MAX);
+        // beyond the cap `init_flash_vault` enforces, the fee saturates instead of wrapping
+        assert_eq!(vault(u16::MAX).fee(u64::MAX), u64::MAX);
+    }
+}
@@ -1,58 +1,150 @@
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::instruction::Instruction;
-use anchor_spl::token::TokenAccount;
+use anchor_lang::InstructionData;
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::TokenAccount;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program;
+use jupiter_cpi::{find_event_authority, find_jupiter_program_authority, jupiter_override};
+use crate::error::ErrorCode;
+use crate::ix_data::JupiterRoute;
+use crate::state::SwapState;
+use crate::treasury::Custody;
 
-// Import necessary types from jupiter-cpi crate
-use jupiter_cpi::{
-    JupiterIx, JupiterIxParams, JupiterIxResult, Route, SwapMode,
-};
-
-pub fn jupiter_swap<'info>(
-    ctx: Context<'_, '_, '_, 'info, JupiterSwap<'info>>,
+pub fn _jupiter_swap<'info>(
+    ctx: &Context<'_, '_, '_, 'info, JupiterSwap<'info>>,
     amount_in: u64,
     minimum_amount_out: u64,
+    route: JupiterRoute,
 ) -> Result<()> {
-    // Create JupiterIxParams
-    let params = JupiterIxParams {
-        amount_in,
-        minimum_amount_out,
-        route: ctx.accounts.route.clone(),
-        swap_mode: SwapMode::ExactIn,
-        platform_fee: None, // Set platform fee if needed
-        token_ledger: None, // Set token ledger if needed
+    require_keys_eq!(
+        ctx.accounts.program_authority.key(),
+        find_jupiter_program_authority(route.id),
+        ErrorCode::InvalidJupiterAuthority
+    );
+
+    // slippage is expressed through `quoted_out_amount`, so no extra bps are allowed
+    let data = jupiter_override::SharedAccountsRoute {
+        id: route.id,
+        route_plan: route
+            .route_plan
+            .into_iter()
+            .map(|step| jupiter_override::RoutePlanStep {
+                swap: step.swap,
+                percent: step.percent,
+                input_index: step.input_index,
+                output_index: step.output_index,
+            })
+            .collect(),
+        in_amount: amount_in,
+        quoted_out_amount: minimum_amount_out,
+        slippage_bps: 0,
+        platform_fee_bps: 0,
     };
 
-    // Create Jupiter instruction
-    let jupiter_ix = JupiterIx::new(params)?;
-
-    // Convert Jupiter instruction to Solana instruction
-    let ix: Instruction = jupiter_ix.into();
-
-    // Invoke the Jupiter swap instruction
-    anchor_lang::solana_program::program::invoke(
-        &ix,
-        &[
-            ctx.accounts.jupiter_program.to_account_info(),
-            ctx.accounts.token_program.to_account_info(),
-            ctx.accounts.user_authority.to_account_info(),
-            ctx.accounts.user_source_token.to_account_info(),
-            ctx.accounts.user_destination_token.to_account_info(),
-            // Add other required accounts based on the route
-        ],
-    )?;
+    let mut ix_accounts = vec![
+        AccountMeta::new_readonly(ctx.accounts.token_program.key(), false),
+        AccountMeta::new_readonly(*ctx.accounts.program_authority.key, false),
+        AccountMeta::new_readonly(ctx.accounts.user_authority.key(), true),
+        AccountMeta::new(ctx.accounts.user_source_token.key(), false),
+        AccountMeta::new(*ctx.accounts.program_source_token_account.key, false),
+        AccountMeta::new(*ctx.accounts.program_destination_token_account.key, false),
+        AccountMeta::new(ctx.accounts.user_destination_token.key(), false),
+        AccountMeta::new_readonly(*ctx.accounts.source_mint.key, false),
+        AccountMeta::new_readonly(*ctx.accounts.destination_mint.key, false),
+        AccountMeta::new(*ctx.accounts.platform_fee_account.key, false),
+        AccountMeta::new_readonly(*ctx.accounts.token_2022_program.key, false),
+        AccountMeta::new_readonly(*ctx.accounts.jupiter_event_authority.key, false),
+        AccountMeta::new_readonly(*ctx.accounts.jupiter_program.key, false),
+    ];
+
+    let mut accounts = vec![
+        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.program_authority.to_account_info(),
+        ctx.accounts.user_authority.to_account_info(),
+        ctx.accounts.user_source_token.to_account_info(),
+        ctx.accounts.program_source_token_account.to_account_info(),
+        ctx.accounts.program_destination_token_account.to_account_info(),
+        ctx.accounts.user_destination_token.to_account_info(),
+        ctx.accounts.source_mint.to_account_info(),
+        ctx.accounts.destination_mint.to_account_info(),
+        ctx.accounts.platform_fee_account.to_account_info(),
+        ctx.accounts.token_2022_program.to_account_info(),
+        ctx.accounts.jupiter_event_authority.to_account_info(),
+        ctx.accounts.jupiter_program.to_account_info(),
+    ];
+
+    // the accounts of every route plan step follow in remaining accounts
+    for account in ctx.remaining_accounts.iter() {
+        ix_accounts.push(AccountMeta {
+            pubkey: *account.key,
+            is_signer: account.is_signer,
+            is_writable: account.is_writable,
+        });
+        accounts.push(account.to_account_info());
+    }
+
+    let instruction = Instruction {
+        program_id: jupiter_cpi::ID,
+        accounts: ix_accounts,
+        data: data.data(),
+    };
+
+    let custody = Custody::of(&ctx.accounts.user_authority, &ctx.accounts.swap_state)?;
+    custody.with_signer(|signer| solana_program::program::invoke_signed(&instruction, &accounts, signer))?;
 
     Ok(())
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct JupiterSwap<'info> {
+    #[account(address = jupiter_cpi::ID @ ErrorCode::UnknownDexProgram)]
+    /// CHECK: This is not dangerous because we check the program id
     pub jupiter_program: AccountInfo<'info>,
-    pub token_program: AccountInfo<'info>,
-    pub user_authority: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    /// CHECK: This is not dangerous because we check it against the route id in `_jupiter_swap`
+    pub program_authority: AccountInfo<'info>,
+    /// CHECK: The swap authority or its treasury PDA, checked by `Custody::of`
+    pub user_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = swap_state.is_custodian(&user_source_token.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_source_token: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
-    pub user_source_token: Account<'info, TokenAccount>,
+    /// CHECK: Source token account of the program authority, checked by jupiter
+    pub program_source_token_account: AccountInfo<'info>,
     #[account(mut)]
-    pub user_destination_token: Account<'info, TokenAccount>,
-    // Add other required accounts based on the route
-    pub route: Route,
-}
\ No newline at end of file
+    /// CHECK: Destination token account of the program authority, checked by jupiter
+    pub program_destination_token_account: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = swap_state.is_custodian(&user_destination_token.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_destination_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = user_source_token.mint)]
+    /// CHECK: This is not dangerous because we check it against the user source token
+    pub source_mint: AccountInfo<'info>,
+    #[account(address = user_destination_token.mint)]
+    /// CHECK: This is not dangerous because we check it against the user destination token
+    pub destination_mint: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Platform fee account, pass the jupiter program id when unused
+    pub platform_fee_account: AccountInfo<'info>,
+    /// CHECK: Token-2022 program, pass the jupiter program id when unused
+    pub token_2022_program: AccountInfo<'info>,
+    #[account(address = find_event_authority())]
+    /// CHECK: This is not dangerous because we check the PDA derivation
+    pub jupiter_event_authority: AccountInfo<'info>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: This is not dangerous because we check the sysvar id
+    pub instructions: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        constraint = swap_state.is_custodian(user_authority.key) @ ErrorCode::InvalidAuthority
+    )]
+    pub swap_state: Account<'info, SwapState>,
+    // Route plan accounts need to be passed using remaining accounts
+}
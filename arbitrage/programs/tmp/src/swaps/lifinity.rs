@@ -0,0 +1,167 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::TokenAccount;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program;
+use crate::error::ErrorCode;
+use crate::ix_data::LifinitySwapData;
+use crate::state::SwapState;
+use crate::swaps::fee_rate;
+use crate::treasury::Custody;
+
+pub const LIFINITY_V2_PROGRAM_ID: Pubkey = solana_program::pubkey!("2wT8Yq49kHgDzXuPxZSaeLaH1qbmGXtEyPy64bL7aD3c");
+
+// sha256("global:swap")[..8]
+const LIFINITY_SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+// sha256("account:Amm")[..8]
+const LIFINITY_AMM_DISCRIMINATOR: [u8; 8] = [143, 245, 200, 17, 74, 214, 196, 135];
+
+/// The fee fields of a Lifinity v2 `Amm` account.
+pub struct LifinityAmm {
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+    pub owner_trade_fee_numerator: u64,
+    pub owner_trade_fee_denominator: u64,
+}
+
+impl LifinityAmm {
+    // discriminator and the fixed fields up to the end of `AmmFees::owner_trade_fee_denominator`
+    pub const LEN: usize = 478;
+
+    pub fn load(amm: &AccountInfo) -> Result<Self> {
+        let data = amm.try_borrow_data()?;
+        require!(data.len() >= Self::LEN, ErrorCode::InvalidLifinityPool);
+        require!(data[..8] == LIFINITY_AMM_DISCRIMINATOR, ErrorCode::InvalidLifinityPool);
+
+        let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+
+        Ok(Self {
+            trade_fee_numerator: u64_at(446),
+            trade_fee_denominator: u64_at(454),
+            owner_trade_fee_numerator: u64_at(462),
+            owner_trade_fee_denominator: u64_at(470),
+        })
+    }
+}
+
+// as in the token swap it forks, the pool and its owner both take a cut of the input
+pub fn lifinity_fee_rate(accounts: &LifinitySwap) -> Result<u64> {
+    let amm = LifinityAmm::load(&accounts.amm)?;
+    Ok(fee_rate(amm.trade_fee_numerator, amm.trade_fee_denominator)
+        + fee_rate(amm.owner_trade_fee_numerator, amm.owner_trade_fee_denominator))
+}
+
+pub fn _lifinity_swap<'info>(
+    ctx: &Context<'_, '_, '_, 'info, LifinitySwap<'info>>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<()> {
+    let data = LifinitySwapData {
+        discriminator: LIFINITY_SWAP_DISCRIMINATOR,
+        amount_in,
+        minimum_amount_out,
+    };
+
+    let ix_accounts = vec![
+        AccountMeta::new_readonly(*ctx.accounts.authority.key, false),
+        AccountMeta::new(*ctx.accounts.amm.key, false),
+        AccountMeta::new_readonly(ctx.accounts.user_transfer_authority.key(), true),
+
+        AccountMeta::new(ctx.accounts.user_src.key(), false),
+        AccountMeta::new(ctx.accounts.user_dst.key(), false),
+        AccountMeta::new(*ctx.accounts.pool_src.key, false),
+        AccountMeta::new(*ctx.accounts.pool_dst.key, false),
+        AccountMeta::new(*ctx.accounts.pool_mint.key, false),
+        AccountMeta::new(*ctx.accounts.fee_account.key, false),
+
+        AccountMeta::new_readonly(ctx.accounts.token_program.key(), false),
+        AccountMeta::new_readonly(*ctx.accounts.oracle_main_account.key, false),
+        AccountMeta::new_readonly(*ctx.accounts.oracle_sub_account.key, false),
+        AccountMeta::new_readonly(*ctx.accounts.oracle_pc_account.key, false),
+    ];
+
+    let instruction = Instruction {
+        program_id: *ctx.accounts.lifinity_program.key,
+        accounts: ix_accounts,
+        data: data.try_to_vec()?,
+    };
+
+    let accounts = vec![
+        ctx.accounts.authority.to_account_info(),
+        ctx.accounts.amm.to_account_info(),
+        ctx.accounts.user_transfer_authority.to_account_info(),
+        ctx.accounts.user_src.to_account_info(),
+        ctx.accounts.user_dst.to_account_info(),
+        ctx.accounts.pool_src.to_account_info(),
+        ctx.accounts.pool_dst.to_account_info(),
+        ctx.accounts.pool_mint.to_account_info(),
+        ctx.accounts.fee_account.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.oracle_main_account.to_account_info(),
+        ctx.accounts.oracle_sub_account.to_account_info(),
+        ctx.accounts.oracle_pc_account.to_account_info(),
+        ctx.accounts.lifinity_program.to_account_info(),
+    ];
+
+    let custody = Custody::of(&ctx.accounts.user_transfer_authority, &ctx.accounts.swap_state)?;
+    custody.with_signer(|signer| solana_program::program::invoke_signed(&instruction, &accounts, signer))?;
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct LifinitySwap<'info> {
+    #[account(address = LIFINITY_V2_PROGRAM_ID @ ErrorCode::UnknownDexProgram)]
+    /// CHECK: This is not dangerous because we check the program id
+    pub lifinity_program: AccountInfo<'info>,
+    #[account(seeds = [amm.key().as_ref()], bump, seeds::program = LIFINITY_V2_PROGRAM_ID)]
+    /// CHECK: This is not dangerous because we check the PDA derivation
+    pub authority: AccountInfo<'info>,
+    #[account(mut, owner = LIFINITY_V2_PROGRAM_ID)]
+    /// CHECK: This is not dangerous because we check the owner, the fees are parsed by `LifinityAmm::load`
+    pub amm: AccountInfo<'info>,
+    /// CHECK: The swap authority or its treasury PDA, checked by `Custody::of`
+    pub user_transfer_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::token_program = token_program,
+        constraint = swap_state.is_custodian(&user_src.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_src: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::token_program = token_program,
+        constraint = swap_state.is_custodian(&user_dst.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_dst: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub pool_src: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub pool_dst: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub pool_mint: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub fee_account: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub oracle_main_account: AccountInfo<'info>,
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub oracle_sub_account: AccountInfo<'info>,
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub oracle_pc_account: AccountInfo<'info>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: This is not dangerous because we check the sysvar id
+    pub instructions: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        constraint = swap_state.is_custodian(user_transfer_authority.key) @ ErrorCode::InvalidAuthority
+    )]
+    pub swap_state: Account<'info, SwapState>,
+}
@@ -0,0 +1,165 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use dlmm::state::lb_pair::LbPair;
+use crate::error::ErrorCode;
+use crate::state::SwapState;
+use crate::swaps::{trades_pair, FEE_RATE_DENOMINATOR};
+use crate::treasury::Custody;
+
+const BASIS_POINT_MAX: u64 = 10_000;
+const MAX_FEE_RATE: u128 = 100_000_000;
+/// Precision of DLMM fee rates.
+pub const DLMM_FEE_PRECISION: u128 = 1_000_000_000;
+
+/// The volatility references the pair resets to at the start of a swap at `now`,
+/// as `(index_reference, volatility_reference)`.
+pub fn volatility_references(lb_pair: &LbPair, now: i64) -> (i32, u32) {
+    let parameters = &lb_pair.parameters;
+    let v_parameters = &lb_pair.v_parameters;
+    let elapsed = now.saturating_sub(v_parameters.last_update_timestamp);
+    if elapsed < parameters.filter_period as i64 {
+        return (v_parameters.index_reference, v_parameters.volatility_reference);
+    }
+    let volatility_reference = if elapsed < parameters.decay_period as i64 {
+        (v_parameters.volatility_accumulator as u64 * parameters.reduction_factor as u64 / BASIS_POINT_MAX) as u32
+    } else {
+        0
+    };
+    (lb_pair.active_id, volatility_reference)
+}
+
+/// Base fee plus the variable fee the pair charges in bin `bin_id`, in `DLMM_FEE_PRECISION`.
+pub fn bin_fee_rate(lb_pair: &LbPair, (index_reference, volatility_reference): (i32, u32), bin_id: i32) -> u128 {
+    let parameters = &lb_pair.parameters;
+    let delta_id = (index_reference as i64 - bin_id as i64).unsigned_abs();
+    let volatility_accumulator = (volatility_reference as u64 + delta_id * BASIS_POINT_MAX)
+        .min(parameters.max_volatility_accumulator as u64) as u128;
+
+    let bin_step = lb_pair.bin_step as u128;
+    let base_fee = parameters.base_factor as u128 * bin_step * 10;
+    let variable_fee_control = parameters.variable_fee_control as u128;
+    let variable_fee = if variable_fee_control > 0 {
+        let square_vfa_bin = (volatility_accumulator * bin_step).pow(2);
+        (variable_fee_control * square_vfa_bin).div_ceil(100_000_000_000)
+    } else {
+        0
+    };
+    (base_fee + variable_fee).min(MAX_FEE_RATE)
+}
+
+// the fee of the active bin, which is where the swap starts
+pub fn meteora_dlmm_fee_rate(accounts: &MeteoraDlmmSwap) -> Result<u64> {
+    let lb_pair = accounts.lb_pair.load()?;
+    let references = volatility_references(&lb_pair, Clock::get()?.unix_timestamp);
+    let rate = bin_fee_rate(&lb_pair, references, lb_pair.active_id);
+    Ok(rate.div_ceil(DLMM_FEE_PRECISION / FEE_RATE_DENOMINATOR as u128) as u64)
+}
+
+pub fn _meteora_dlmm_swap<'info>(
+    ctx: &Context<'_, '_, '_, 'info, MeteoraDlmmSwap<'info>>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<()> {
+    let accounts = dlmm::cpi::accounts::Swap {
+        lb_pair: ctx.accounts.lb_pair.to_account_info(),
+        bin_array_bitmap_extension: ctx
+            .accounts
+            .bin_array_bitmap_extension
+            .as_ref()
+            .map(|account| account.to_account_info()),
+        reserve_x: ctx.accounts.reserve_x.to_account_info(),
+        reserve_y: ctx.accounts.reserve_y.to_account_info(),
+        user_token_in: ctx.accounts.user_token_in.to_account_info(),
+        user_token_out: ctx.accounts.user_token_out.to_account_info(),
+        token_x_mint: ctx.accounts.token_x_mint.to_account_info(),
+        token_y_mint: ctx.accounts.token_y_mint.to_account_info(),
+        oracle: ctx.accounts.oracle.to_account_info(),
+        host_fee_in: ctx
+            .accounts
+            .host_fee_in
+            .as_ref()
+            .map(|account| account.to_account_info()),
+        user: ctx.accounts.user.to_account_info(),
+        token_x_program: ctx.accounts.token_x_program.to_account_info(),
+        token_y_program: ctx.accounts.token_y_program.to_account_info(),
+        event_authority: ctx.accounts.dlmm_event_authority.to_account_info(),
+        program: ctx.accounts.dlmm_program.to_account_info(),
+    };
+
+    // bin arrays traversed by the swap are passed through remaining accounts
+    let custody = Custody::of(&ctx.accounts.user, &ctx.accounts.swap_state)?;
+    custody.with_signer(|signer| {
+        let cpi_context = CpiContext::new_with_signer(ctx.accounts.dlmm_program.to_account_info(), accounts, signer)
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+        dlmm::cpi::swap(cpi_context, amount_in, minimum_amount_out)
+    })
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MeteoraDlmmSwap<'info> {
+    #[account(
+        mut,
+        has_one = reserve_x @ ErrorCode::InvalidMeteoraPool,
+        has_one = reserve_y @ ErrorCode::InvalidMeteoraPool,
+        has_one = token_x_mint @ ErrorCode::InvalidMeteoraPool,
+        has_one = token_y_mint @ ErrorCode::InvalidMeteoraPool,
+        has_one = oracle @ ErrorCode::InvalidMeteoraPool
+    )]
+    pub lb_pair: AccountLoader<'info, LbPair>,
+    /// CHECK: Bin array extension account of the pool
+    pub bin_array_bitmap_extension: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    /// CHECK: Reserve account of token X, checked against the pool
+    pub reserve_x: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Reserve account of token Y, checked against the pool
+    pub reserve_y: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = swap_state.is_custodian(&user_token_in.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_token_in: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = trades_pair(
+            &user_token_in.mint,
+            &user_token_out.mint,
+            &token_x_mint.key(),
+            &token_y_mint.key()
+        ) @ ErrorCode::InvalidMeteoraPool,
+        constraint = swap_state.is_custodian(&user_token_out.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_token_out: InterfaceAccount<'info, TokenAccount>,
+    #[account(mint::token_program = token_x_program)]
+    pub token_x_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = token_y_program)]
+    pub token_y_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut)]
+    /// CHECK: Oracle account of the pool, checked against the pool
+    pub oracle: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Referral fee account
+    pub host_fee_in: Option<AccountInfo<'info>>,
+    /// CHECK: The swap authority or its treasury PDA, checked by `Custody::of`
+    pub user: AccountInfo<'info>,
+    #[account(address = dlmm::ID @ ErrorCode::UnknownDexProgram)]
+    /// CHECK: DLMM program
+    pub dlmm_program: AccountInfo<'info>,
+    #[account(seeds = [b"__event_authority"], bump, seeds::program = dlmm::ID)]
+    /// CHECK: DLMM program event authority for event CPI
+    pub dlmm_event_authority: AccountInfo<'info>,
+    pub token_x_program: Interface<'info, TokenInterface>,
+    pub token_y_program: Interface<'info, TokenInterface>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: This is not dangerous because we check the sysvar id
+    pub instructions: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        constraint = swap_state.is_custodian(user.key) @ ErrorCode::InvalidAuthority
+    )]
+    pub swap_state: Account<'info, SwapState>,
+    // Bin arrays need to be passed using remaining accounts
+}
@@ -0,0 +1,136 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::TokenAccount;
+use crate::error::ErrorCode;
+use crate::state::SwapState;
+use crate::swaps::{fee_rate, trades_pair};
+use crate::treasury::Custody;
+use dynamic_amm::state::Pool;
+
+pub const METEORA_VAULT_PROGRAM_ID: Pubkey = solana_program::pubkey!("24Uqj9JCLxUeoC3hGfh5W3s9FM9uCHDS2SG3LYwBpyTi");
+
+pub fn _meteora_dynamic_amm_swap<'info>(
+    ctx: &Context<'_, '_, '_, 'info, MeteoraDynamicAmmSwap<'info>>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<()> {
+    check_dynamic_amm_pool(ctx.accounts)?;
+
+    let accounts = dynamic_amm::cpi::accounts::Swap {
+        pool: ctx.accounts.pool.to_account_info(),
+        user_source_token: ctx.accounts.user_source_token.to_account_info(),
+        user_destination_token: ctx.accounts.user_destination_token.to_account_info(),
+        a_vault: ctx.accounts.a_vault.to_account_info(),
+        b_vault: ctx.accounts.b_vault.to_account_info(),
+        a_token_vault: ctx.accounts.a_token_vault.to_account_info(),
+        b_token_vault: ctx.accounts.b_token_vault.to_account_info(),
+        a_vault_lp_mint: ctx.accounts.a_vault_lp_mint.to_account_info(),
+        b_vault_lp_mint: ctx.accounts.b_vault_lp_mint.to_account_info(),
+        a_vault_lp: ctx.accounts.a_vault_lp.to_account_info(),
+        b_vault_lp: ctx.accounts.b_vault_lp.to_account_info(),
+        admin_token_fee: ctx.accounts.admin_token_fee.to_account_info(),
+        user: ctx.accounts.user.to_account_info(),
+        vault_program: ctx.accounts.vault_program.to_account_info(),
+        token_program: ctx.accounts.token_program.to_account_info(),
+    };
+
+    let custody = Custody::of(&ctx.accounts.user, &ctx.accounts.swap_state)?;
+    custody.with_signer(|signer| {
+        let cpi_context = CpiContext::new_with_signer(ctx.accounts.dynamic_amm_program.to_account_info(), accounts, signer);
+        dynamic_amm::cpi::swap(cpi_context, amount_in, minimum_amount_out)
+    })
+}
+
+pub fn meteora_dynamic_amm_fee_rate(accounts: &MeteoraDynamicAmmSwap) -> u64 {
+    let fees = &accounts.pool.fees;
+    fee_rate(fees.trade_fee_numerator, fees.trade_fee_denominator)
+}
+
+fn check_dynamic_amm_pool(accounts: &MeteoraDynamicAmmSwap) -> Result<()> {
+    let pool = &accounts.pool;
+    let src_mint = accounts.user_source_token.mint;
+    require!(
+        trades_pair(&src_mint, &accounts.user_destination_token.mint, &pool.token_a_mint, &pool.token_b_mint),
+        ErrorCode::InvalidMeteoraPool
+    );
+
+    // the admin fee is taken in the input token
+    let admin_token_fee = if src_mint == pool.token_a_mint {
+        pool.admin_token_a_fee
+    } else {
+        pool.admin_token_b_fee
+    };
+    require_keys_eq!(accounts.admin_token_fee.key(), admin_token_fee, ErrorCode::InvalidMeteoraPool);
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MeteoraDynamicAmmSwap<'info> {
+    #[account(
+        mut,
+        has_one = a_vault @ ErrorCode::InvalidMeteoraPool,
+        has_one = b_vault @ ErrorCode::InvalidMeteoraPool,
+        has_one = a_vault_lp @ ErrorCode::InvalidMeteoraPool,
+        has_one = b_vault_lp @ ErrorCode::InvalidMeteoraPool,
+    )]
+    pub pool: Box<Account<'info, Pool>>,
+    #[account(
+        mut,
+        token::token_program = token_program,
+        constraint = swap_state.is_custodian(&user_source_token.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_source_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::token_program = token_program,
+        constraint = swap_state.is_custodian(&user_destination_token.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_destination_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    /// CHECK: Vault account for token a, checked against the pool. token a of the pool will be deposit / withdraw from this vault account.
+    pub a_vault: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Vault account for token b, checked against the pool. token b of the pool will be deposit / withdraw from this vault account.
+    pub b_vault: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Token vault account of vault A
+    pub a_token_vault: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Token vault account of vault B
+    pub b_token_vault: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Lp token mint of vault a
+    pub a_vault_lp_mint: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Lp token mint of vault b
+    pub b_vault_lp_mint: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: LP token account of vault A, checked against the pool. Used to receive/burn the vault LP upon deposit/withdraw from the vault.
+    pub a_vault_lp: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: LP token account of vault B, checked against the pool. Used to receive/burn the vault LP upon deposit/withdraw from the vault.
+    pub b_vault_lp: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: Admin fee token account, checked against the pool. Used to receive trading fee. It's mint field must matched with user_source_token mint field.
+    pub admin_token_fee: AccountInfo<'info>,
+    /// CHECK: The swap authority or its treasury PDA, checked by `Custody::of`
+    pub user: AccountInfo<'info>,
+    #[account(address = METEORA_VAULT_PROGRAM_ID @ ErrorCode::UnknownDexProgram)]
+    /// CHECK: Vault program. the pool will deposit/withdraw liquidity from the vault.
+    pub vault_program: AccountInfo<'info>,
+    /// CHECK: Token program.
+    pub token_program: AccountInfo<'info>,
+    #[account(address = dynamic_amm::ID @ ErrorCode::UnknownDexProgram)]
+    /// CHECK: Dynamic AMM program account
+    pub dynamic_amm_program: AccountInfo<'info>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: This is not dangerous because we check the sysvar id
+    pub instructions: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        constraint = swap_state.is_custodian(user.key) @ ErrorCode::InvalidAuthority
+    )]
+    pub swap_state: Account<'info, SwapState>,
+}
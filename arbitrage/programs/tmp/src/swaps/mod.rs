@@ -1,11 +1,59 @@
 // File: program/src/swaps/mod.rs
 
 pub mod orca;
+pub mod whirlpool;
 pub mod raydium;
-pub mod meteora;
+pub mod meteora_dlmm;
+pub mod meteora_dynamic_amm;
+pub mod phoenix;
+pub mod lifinity;
 pub mod jupiter;
 
 pub use orca::*;
+pub use whirlpool::*;
 pub use raydium::*;
-pub use meteora::*;
-pub use jupiter::*;
\ No newline at end of file
+pub use meteora_dlmm::*;
+pub use meteora_dynamic_amm::*;
+pub use phoenix::*;
+pub use lifinity::*;
+pub use jupiter::*;
+
+use anchor_lang::prelude::Pubkey;
+
+/// Precision of the fee rates legs report, hundredths of a basis point as in whirlpools.
+pub const FEE_RATE_DENOMINATOR: u64 = 1_000_000;
+
+/// `numerator / denominator` as a rate over `FEE_RATE_DENOMINATOR`, rounded up.
+pub fn fee_rate(numerator: u64, denominator: u64) -> u64 {
+    if denominator == 0 {
+        return 0;
+    }
+    let rate = (numerator as u128 * FEE_RATE_DENOMINATOR as u128).div_ceil(denominator as u128);
+    rate.min(FEE_RATE_DENOMINATOR as u128) as u64
+}
+
+/// Whether a leg trades one side of the `mint_a`/`mint_b` pool for the other.
+pub fn trades_pair(src_mint: &Pubkey, dst_mint: &Pubkey, mint_a: &Pubkey, mint_b: &Pubkey) -> bool {
+    (src_mint == mint_a && dst_mint == mint_b) || (src_mint == mint_b && dst_mint == mint_a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_rate_rounds_up() {
+        // 25 / 10_000 is 0.25%
+        assert_eq!(fee_rate(25, 10_000), 2_500);
+        assert_eq!(fee_rate(1, 3), 333_334);
+        assert_eq!(fee_rate(1, u64::MAX), 1);
+        assert_eq!(fee_rate(0, 10_000), 0);
+    }
+
+    #[test]
+    fn fee_rate_is_capped_and_survives_a_zero_denominator() {
+        assert_eq!(fee_rate(3, 2), FEE_RATE_DENOMINATOR);
+        assert_eq!(fee_rate(u64::MAX, 1), FEE_RATE_DENOMINATOR);
+        assert_eq!(fee_rate(25, 0), 0);
+    }
+}
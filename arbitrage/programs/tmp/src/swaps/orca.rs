@@ -1,17 +1,108 @@
 use anchor_lang::prelude::*;
 use solana_program::instruction::{AccountMeta, Instruction};
 use solana_program;
-use anchor_spl:: token::TokenAccount;
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::TokenAccount;
 use anchor_lang::Accounts;
 use crate::ix_data::SwapData;
+use crate::error::ErrorCode;
 use crate::state::SwapState;
+use crate::swaps::{fee_rate, trades_pair};
+use crate::treasury::Custody;
+
+// token swap v2 and v1, both still host legacy orca pools
+pub const ORCA_TOKEN_SWAP_PROGRAM_IDS: [Pubkey; 2] = [
+    solana_program::pubkey!("9W959DqEETiGZocYWCQPaJ6sBmUzgfxXfqGeTEdp3aQP"),
+    solana_program::pubkey!("DjVE6JNiYqPL2QXyCUUh8rNjHrbz9hXHNYt99MQ59qw1"),
+];
+
+/// The fields of the spl-token-swap `SwapV1` state needed to route a swap.
+pub struct OrcaTokenSwap {
+    pub is_initialized: bool,
+    pub bump_seed: u8,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub pool_mint: Pubkey,
+    pub token_a_mint: Pubkey,
+    pub token_b_mint: Pubkey,
+    pub pool_fee_account: Pubkey,
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+    pub owner_trade_fee_numerator: u64,
+    pub owner_trade_fee_denominator: u64,
+}
+
+impl OrcaTokenSwap {
+    // 1 byte version tag followed by the packed `SwapV1`
+    pub const LEN: usize = 324;
+
+    pub fn load(token_swap: &AccountInfo) -> Result<Self> {
+        let data = token_swap.try_borrow_data()?;
+        require!(data.len() >= Self::LEN, ErrorCode::InvalidOrcaPool);
+
+        let pubkey_at = |offset: usize| Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap());
+        let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+
+        let state = Self {
+            is_initialized: data[1] == 1,
+            bump_seed: data[2],
+            token_a: pubkey_at(35),
+            token_b: pubkey_at(67),
+            pool_mint: pubkey_at(99),
+            token_a_mint: pubkey_at(131),
+            token_b_mint: pubkey_at(163),
+            pool_fee_account: pubkey_at(195),
+            trade_fee_numerator: u64_at(227),
+            trade_fee_denominator: u64_at(235),
+            owner_trade_fee_numerator: u64_at(243),
+            owner_trade_fee_denominator: u64_at(251),
+        };
+        require!(state.is_initialized, ErrorCode::InvalidOrcaPool);
+        Ok(state)
+    }
+}
+
+// the pool and its owner both take a cut of the input
+pub fn orca_fee_rate(accounts: &OrcaSwap) -> Result<u64> {
+    let state = OrcaTokenSwap::load(&accounts.token_swap)?;
+    Ok(fee_rate(state.trade_fee_numerator, state.trade_fee_denominator)
+        + fee_rate(state.owner_trade_fee_numerator, state.owner_trade_fee_denominator))
+}
+
+fn check_orca_pool(accounts: &OrcaSwap) -> Result<()> {
+    let state = OrcaTokenSwap::load(&accounts.token_swap)?;
+    let authority = Pubkey::create_program_address(
+        &[accounts.token_swap.key.as_ref(), &[state.bump_seed]],
+        accounts.token_swap_program.key,
+    ).map_err(|_| error!(ErrorCode::InvalidOrcaPool))?;
+    require_keys_eq!(accounts.authority.key(), authority, ErrorCode::InvalidOrcaPool);
+    require_keys_eq!(accounts.pool_mint.key(), state.pool_mint, ErrorCode::InvalidOrcaPool);
+    require_keys_eq!(accounts.fee_account.key(), state.pool_fee_account, ErrorCode::InvalidOrcaPool);
+
+    let src_mint = accounts.user_src.mint;
+    require!(
+        trades_pair(&src_mint, &accounts.user_dst.mint, &state.token_a_mint, &state.token_b_mint),
+        ErrorCode::InvalidOrcaPool
+    );
+
+    // the pool vaults follow the direction of the trade
+    let (pool_src, pool_dst) = if src_mint == state.token_a_mint {
+        (state.token_a, state.token_b)
+    } else {
+        (state.token_b, state.token_a)
+    };
+    require_keys_eq!(accounts.pool_src.key(), pool_src, ErrorCode::InvalidOrcaPool);
+    require_keys_eq!(accounts.pool_dst.key(), pool_dst, ErrorCode::InvalidOrcaPool);
+    Ok(())
+}
 
 pub fn _orca_swap<'info>(
     ctx: &Context<'_, '_, '_, 'info, OrcaSwap<'info>>, 
     amount_in: u64,
     minimum_amount_out: u64
 ) -> Result<()> {
-    
+    check_orca_pool(ctx.accounts)?;
+
     let data = SwapData {
         instruction: 1, // swap instruction 
         amount_in,
@@ -53,41 +144,58 @@ pub fn _orca_swap<'info>(
         ctx.accounts.token_swap_program.to_account_info(),
     ];
 
-    solana_program::program::invoke(
-        &instruction, 
-        &accounts, 
-    )?;
+    let custody = Custody::of(&ctx.accounts.user_transfer_authority, &ctx.accounts.swap_state)?;
+    custody.with_signer(|signer| solana_program::program::invoke_signed(&instruction, &accounts, signer))?;
     
     Ok(())
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct OrcaSwap<'info> {
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(owner = token_swap_program.key() @ ErrorCode::InvalidOrcaPool)]
+    /// CHECK: The token swap state is parsed and checked in `_orca_swap`
     pub token_swap: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    /// CHECK: This is not dangerous because we check the PDA against the swap bump seed
     pub authority: AccountInfo<'info>,
-    pub user_transfer_authority: Signer<'info>,
+    /// CHECK: The swap authority or its treasury PDA, checked by `Custody::of`
+    pub user_transfer_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::token_program = token_program,
+        constraint = swap_state.is_custodian(&user_src.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_src: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
-    pub user_src: Account<'info, TokenAccount>,
-    #[account(mut)]
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    /// CHECK: This is not dangerous because we check it against the swap state
     pub pool_src: AccountInfo<'info>,
     #[account(mut)]
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    /// CHECK: This is not dangerous because we check it against the swap state
     pub pool_dst: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::token_program = token_program,
+        constraint = swap_state.is_custodian(&user_dst.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_dst: InterfaceAccount<'info, TokenAccount>,
     #[account(mut)]
-    pub user_dst: Account<'info, TokenAccount>,
-    #[account(mut)]
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    /// CHECK: This is not dangerous because we check it against the swap state
     pub pool_mint: AccountInfo<'info>,
     #[account(mut)]
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    /// CHECK: This is not dangerous because we check it against the swap state
     pub fee_account: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
-    pub token_program: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub token_program: Program<'info, Token>,
+    #[account(constraint = ORCA_TOKEN_SWAP_PROGRAM_IDS.contains(token_swap_program.key) @ ErrorCode::UnknownDexProgram)]
+    /// CHECK: This is not dangerous because we check the program id
     pub token_swap_program: AccountInfo<'info>,
-    #[account(mut, seeds=[b"swap_state"], bump)] 
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: This is not dangerous because we check the sysvar id
+    pub instructions: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        constraint = swap_state.is_custodian(user_transfer_authority.key) @ ErrorCode::InvalidAuthority
+    )]
     pub swap_state: Account<'info, SwapState>,
 }
\ No newline at end of file
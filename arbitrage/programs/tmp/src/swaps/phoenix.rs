@@ -0,0 +1,211 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::TokenAccount;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program;
+use crate::error::ErrorCode;
+use crate::ix_data::PhoenixSwapData;
+use crate::state::SwapState;
+use crate::swaps::FEE_RATE_DENOMINATOR;
+use crate::treasury::Custody;
+
+pub const PHOENIX_PROGRAM_ID: Pubkey = solana_program::pubkey!("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY");
+
+// Phoenix instruction and order packet tags
+const PHOENIX_SWAP_INSTRUCTION: u8 = 0;
+const ORDER_PACKET_IMMEDIATE_OR_CANCEL: u8 = 2;
+const SIDE_BID: u8 = 0;
+const SIDE_ASK: u8 = 1;
+const SELF_TRADE_ABORT: u8 = 0;
+
+/// The fields of the Phoenix `MarketHeader` needed to route a swap.
+pub struct PhoenixMarketHeader {
+    pub base_mint: Pubkey,
+    pub base_vault: Pubkey,
+    pub base_lot_size: u64,
+    pub quote_mint: Pubkey,
+    pub quote_vault: Pubkey,
+    pub quote_lot_size: u64,
+}
+
+// `FIFOMarket::taker_fee_bps`, after the 576 byte header and 32 words of padding
+const TAKER_FEE_BPS_OFFSET: usize = 856;
+
+impl PhoenixMarketHeader {
+    pub const LEN: usize = 208;
+
+    pub fn load(market: &AccountInfo) -> Result<Self> {
+        let data = market.try_borrow_data()?;
+        require!(data.len() >= Self::LEN, ErrorCode::InvalidPhoenixMarket);
+
+        let pubkey_at = |offset: usize| Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap());
+        let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+
+        let header = Self {
+            base_mint: pubkey_at(48),
+            base_vault: pubkey_at(80),
+            base_lot_size: u64_at(112),
+            quote_mint: pubkey_at(128),
+            quote_vault: pubkey_at(160),
+            quote_lot_size: u64_at(192),
+        };
+        require!(
+            header.base_lot_size > 0 && header.quote_lot_size > 0,
+            ErrorCode::InvalidPhoenixMarket
+        );
+        Ok(header)
+    }
+}
+
+// makers pay nothing, takers pay on the quote side of the fill
+pub fn phoenix_fee_rate(accounts: &PhoenixSwap) -> Result<u64> {
+    let data = accounts.market.try_borrow_data()?;
+    require!(data.len() >= TAKER_FEE_BPS_OFFSET + 8, ErrorCode::InvalidPhoenixMarket);
+    let taker_fee_bps = u64::from_le_bytes(data[TAKER_FEE_BPS_OFFSET..TAKER_FEE_BPS_OFFSET + 8].try_into().unwrap());
+    Ok(taker_fee_bps.saturating_mul(FEE_RATE_DENOMINATOR / 10_000))
+}
+
+pub fn _phoenix_swap<'info>(
+    ctx: &Context<'_, '_, '_, 'info, PhoenixSwap<'info>>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<()> {
+    let header = PhoenixMarketHeader::load(&ctx.accounts.market)?;
+    require_keys_eq!(ctx.accounts.base_vault.key(), header.base_vault, ErrorCode::InvalidPhoenixMarket);
+    require_keys_eq!(ctx.accounts.quote_vault.key(), header.quote_vault, ErrorCode::InvalidPhoenixMarket);
+
+    // selling base is an ask, buying base with quote is a bid
+    let user_src = &ctx.accounts.user_src;
+    let user_dst = &ctx.accounts.user_dst;
+    let is_ask = user_src.mint == header.base_mint;
+    let data = if is_ask {
+        require_keys_eq!(user_dst.mint, header.quote_mint, ErrorCode::InvalidPhoenixMarket);
+        PhoenixSwapData {
+            side: SIDE_ASK,
+            num_base_lots: amount_in / header.base_lot_size,
+            num_quote_lots: 0,
+            min_base_lots_to_fill: 0,
+            min_quote_lots_to_fill: minimum_amount_out / header.quote_lot_size,
+            ..PhoenixSwapData::ioc()
+        }
+    } else {
+        require_keys_eq!(user_src.mint, header.quote_mint, ErrorCode::InvalidPhoenixMarket);
+        require_keys_eq!(user_dst.mint, header.base_mint, ErrorCode::InvalidPhoenixMarket);
+        PhoenixSwapData {
+            side: SIDE_BID,
+            num_base_lots: 0,
+            num_quote_lots: amount_in / header.quote_lot_size,
+            min_base_lots_to_fill: minimum_amount_out / header.base_lot_size,
+            min_quote_lots_to_fill: 0,
+            ..PhoenixSwapData::ioc()
+        }
+    };
+
+    let (base_account, quote_account) = if is_ask {
+        (user_src.to_account_info(), user_dst.to_account_info())
+    } else {
+        (user_dst.to_account_info(), user_src.to_account_info())
+    };
+
+    let ix_accounts = vec![
+        AccountMeta::new_readonly(*ctx.accounts.phoenix_program.key, false),
+        AccountMeta::new_readonly(*ctx.accounts.log_authority.key, false),
+        AccountMeta::new(*ctx.accounts.market.key, false),
+        AccountMeta::new_readonly(ctx.accounts.trader.key(), true),
+        AccountMeta::new(*base_account.key, false),
+        AccountMeta::new(*quote_account.key, false),
+        AccountMeta::new(*ctx.accounts.base_vault.key, false),
+        AccountMeta::new(*ctx.accounts.quote_vault.key, false),
+        AccountMeta::new_readonly(ctx.accounts.token_program.key(), false),
+    ];
+
+    let instruction = Instruction {
+        program_id: *ctx.accounts.phoenix_program.key,
+        accounts: ix_accounts,
+        data: data.try_to_vec()?,
+    };
+
+    let accounts = vec![
+        ctx.accounts.phoenix_program.to_account_info(),
+        ctx.accounts.log_authority.to_account_info(),
+        ctx.accounts.market.to_account_info(),
+        ctx.accounts.trader.to_account_info(),
+        base_account,
+        quote_account,
+        ctx.accounts.base_vault.to_account_info(),
+        ctx.accounts.quote_vault.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+    ];
+
+    let custody = Custody::of(&ctx.accounts.trader, &ctx.accounts.swap_state)?;
+    custody.with_signer(|signer| solana_program::program::invoke_signed(&instruction, &accounts, signer))?;
+
+    Ok(())
+}
+
+impl PhoenixSwapData {
+    /// Market order that fills what it can and cancels the rest.
+    fn ioc() -> Self {
+        Self {
+            instruction: PHOENIX_SWAP_INSTRUCTION,
+            order_packet: ORDER_PACKET_IMMEDIATE_OR_CANCEL,
+            side: SIDE_BID,
+            price_in_ticks: None,
+            num_base_lots: 0,
+            num_quote_lots: 0,
+            min_base_lots_to_fill: 0,
+            min_quote_lots_to_fill: 0,
+            self_trade_behavior: SELF_TRADE_ABORT,
+            match_limit: None,
+            client_order_id: 0,
+            use_only_deposited_funds: false,
+            last_valid_slot: None,
+            last_valid_unix_timestamp_in_seconds: None,
+        }
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct PhoenixSwap<'info> {
+    #[account(address = PHOENIX_PROGRAM_ID @ ErrorCode::UnknownDexProgram)]
+    /// CHECK: This is not dangerous because we check the program id
+    pub phoenix_program: AccountInfo<'info>,
+    #[account(seeds = [b"log"], bump, seeds::program = PHOENIX_PROGRAM_ID)]
+    /// CHECK: This is not dangerous because we check the PDA derivation
+    pub log_authority: AccountInfo<'info>,
+    #[account(mut, owner = PHOENIX_PROGRAM_ID)]
+    /// CHECK: The market header is parsed and checked in `_phoenix_swap`
+    pub market: AccountInfo<'info>,
+    /// CHECK: The swap authority or its treasury PDA, checked by `Custody::of`
+    pub trader: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::token_program = token_program,
+        constraint = swap_state.is_custodian(&user_src.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_src: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::token_program = token_program,
+        constraint = swap_state.is_custodian(&user_dst.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_dst: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we check it against the market header
+    pub base_vault: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we check it against the market header
+    pub quote_vault: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: This is not dangerous because we check the sysvar id
+    pub instructions: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        constraint = swap_state.is_custodian(trader.key) @ ErrorCode::InvalidAuthority
+    )]
+    pub swap_state: Account<'info, SwapState>,
+}
@@ -1,97 +1,240 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::TokenAccount;
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::TokenAccount;
 use solana_program::instruction::{AccountMeta, Instruction};
+use crate::error::ErrorCode;
+use crate::ix_data::SwapData;
 use crate::state::SwapState;
+use crate::swaps::{fee_rate, trades_pair};
+use crate::treasury::Custody;
+
+pub const RAYDIUM_AMM_V4_PROGRAM_ID: Pubkey = solana_program::pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
+// openbook and the original serum v3 deployment
+pub const SERUM_PROGRAM_IDS: [Pubkey; 2] = [
+    solana_program::pubkey!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX"),
+    solana_program::pubkey!("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin"),
+];
+
+// AMM v4 `swap_base_in` instruction tag
+const RAYDIUM_SWAP_BASE_IN_INSTRUCTION: u8 = 9;
+
+/// The fields of the Raydium AMM v4 `AmmInfo` needed to route a swap.
+pub struct RaydiumAmmInfo {
+    pub nonce: u64,
+    pub trade_fee_numerator: u64,
+    pub trade_fee_denominator: u64,
+    pub swap_fee_numerator: u64,
+    pub swap_fee_denominator: u64,
+    pub need_take_pnl_coin: u64,
+    pub need_take_pnl_pc: u64,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub open_orders: Pubkey,
+    pub market: Pubkey,
+    pub market_program: Pubkey,
+    pub target_orders: Pubkey,
+}
+
+impl RaydiumAmmInfo {
+    pub const LEN: usize = 752;
+
+    pub fn load(amm: &AccountInfo) -> Result<Self> {
+        let data = amm.try_borrow_data()?;
+        require!(data.len() == Self::LEN, ErrorCode::InvalidRaydiumPool);
+
+        let pubkey_at = |offset: usize| Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap());
+        let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+
+        Ok(Self {
+            nonce: u64_at(8),
+            trade_fee_numerator: u64_at(144),
+            trade_fee_denominator: u64_at(152),
+            swap_fee_numerator: u64_at(176),
+            swap_fee_denominator: u64_at(184),
+            need_take_pnl_coin: u64_at(192),
+            need_take_pnl_pc: u64_at(200),
+            coin_vault: pubkey_at(336),
+            pc_vault: pubkey_at(368),
+            coin_mint: pubkey_at(400),
+            pc_mint: pubkey_at(432),
+            open_orders: pubkey_at(496),
+            market: pubkey_at(528),
+            market_program: pubkey_at(560),
+            target_orders: pubkey_at(592),
+        })
+    }
+}
+
+pub(crate) fn check_raydium_pool(accounts: &RaydiumSwap) -> Result<RaydiumAmmInfo> {
+    let info = RaydiumAmmInfo::load(&accounts.amm)?;
+    let amm_authority = Pubkey::create_program_address(
+        &[b"amm authority", &[info.nonce as u8]],
+        &RAYDIUM_AMM_V4_PROGRAM_ID,
+    ).map_err(|_| error!(ErrorCode::InvalidRaydiumPool))?;
+    require_keys_eq!(accounts.amm_authority.key(), amm_authority, ErrorCode::InvalidRaydiumPool);
+    require_keys_eq!(accounts.amm_open_orders.key(), info.open_orders, ErrorCode::InvalidRaydiumPool);
+    require_keys_eq!(accounts.amm_target_orders.key(), info.target_orders, ErrorCode::InvalidRaydiumPool);
+    require_keys_eq!(accounts.pool_coin_token_account.key(), info.coin_vault, ErrorCode::InvalidRaydiumPool);
+    require_keys_eq!(accounts.pool_pc_token_account.key(), info.pc_vault, ErrorCode::InvalidRaydiumPool);
+    require_keys_eq!(accounts.serum_program.key(), info.market_program, ErrorCode::InvalidRaydiumPool);
+    require_keys_eq!(accounts.serum_market.key(), info.market, ErrorCode::InvalidRaydiumPool);
+
+    require!(
+        trades_pair(
+            &accounts.user_source_token_account.mint,
+            &accounts.user_destination_token_account.mint,
+            &info.coin_mint,
+            &info.pc_mint
+        ),
+        ErrorCode::InvalidRaydiumPool
+    );
+    Ok(info)
+}
+
+// swaps are charged the swap fee, the trade fee applies to the order book
+pub fn raydium_fee_rate(accounts: &RaydiumSwap) -> Result<u64> {
+    let info = RaydiumAmmInfo::load(&accounts.amm)?;
+    Ok(fee_rate(info.swap_fee_numerator, info.swap_fee_denominator))
+}
 
 pub fn _raydium_swap<'info>(
     ctx: &Context<'_, '_, '_, 'info, RaydiumSwap<'info>>,
     amount_in: u64,
     minimum_amount_out: u64,
 ) -> Result<()> {
-    let ix = raydium_contract::instruction::swap(
-        ctx.accounts.amm_program.key,
-        ctx.accounts.amm_authority.key,
-        ctx.accounts.amm_open_orders.key,
-        ctx.accounts.amm_target_orders.key,
-        ctx.accounts.pool_coin_token_account.key,
-        ctx.accounts.pool_pc_token_account.key,
-        ctx.accounts.serum_program.key,
-        ctx.accounts.serum_market.key,
-        ctx.accounts.serum_bids.key,
-        ctx.accounts.serum_asks.key,
-        ctx.accounts.serum_event_queue.key,
-        ctx.accounts.serum_coin_vault_account.key,
-        ctx.accounts.serum_pc_vault_account.key,
-        ctx.accounts.serum_vault_signer.key,
-        ctx.accounts.user_source_token_account.key,
-        ctx.accounts.user_destination_token_account.key,
-        ctx.accounts.user_source_owner.key,
+    check_raydium_pool(ctx.accounts)?;
+
+    let data = SwapData {
+        instruction: RAYDIUM_SWAP_BASE_IN_INSTRUCTION,
         amount_in,
         minimum_amount_out,
-    )?;
-
-    solana_program::program::invoke(
-        &ix,
-        &[
-            ctx.accounts.amm_program.to_account_info(),
-            ctx.accounts.amm_authority.to_account_info(),
-            ctx.accounts.amm_open_orders.to_account_info(),
-            ctx.accounts.amm_target_orders.to_account_info(),
-            ctx.accounts.pool_coin_token_account.to_account_info(),
-            ctx.accounts.pool_pc_token_account.to_account_info(),
-            ctx.accounts.serum_program.to_account_info(),
-            ctx.accounts.serum_market.to_account_info(),
-            ctx.accounts.serum_bids.to_account_info(),
-            ctx.accounts.serum_asks.to_account_info(),
-            ctx.accounts.serum_event_queue.to_account_info(),
-            ctx.accounts.serum_coin_vault_account.to_account_info(),
-            ctx.accounts.serum_pc_vault_account.to_account_info(),
-            ctx.accounts.serum_vault_signer.to_account_info(),
-            ctx.accounts.user_source_token_account.to_account_info(),
-            ctx.accounts.user_destination_token_account.to_account_info(),
-            ctx.accounts.user_source_owner.to_account_info(),
-            ctx.accounts.token_program.to_account_info(),
-        ],
-    )?;
+    };
+
+    let ix_accounts = vec![
+        AccountMeta::new_readonly(ctx.accounts.token_program.key(), false),
+        AccountMeta::new(*ctx.accounts.amm.key, false),
+        AccountMeta::new_readonly(*ctx.accounts.amm_authority.key, false),
+        AccountMeta::new(*ctx.accounts.amm_open_orders.key, false),
+        AccountMeta::new(*ctx.accounts.amm_target_orders.key, false),
+        AccountMeta::new(*ctx.accounts.pool_coin_token_account.key, false),
+        AccountMeta::new(*ctx.accounts.pool_pc_token_account.key, false),
+        AccountMeta::new_readonly(*ctx.accounts.serum_program.key, false),
+        AccountMeta::new(*ctx.accounts.serum_market.key, false),
+        AccountMeta::new(*ctx.accounts.serum_bids.key, false),
+        AccountMeta::new(*ctx.accounts.serum_asks.key, false),
+        AccountMeta::new(*ctx.accounts.serum_event_queue.key, false),
+        AccountMeta::new(*ctx.accounts.serum_coin_vault_account.key, false),
+        AccountMeta::new(*ctx.accounts.serum_pc_vault_account.key, false),
+        AccountMeta::new_readonly(*ctx.accounts.serum_vault_signer.key, false),
+        AccountMeta::new(ctx.accounts.user_source_token_account.key(), false),
+        AccountMeta::new(ctx.accounts.user_destination_token_account.key(), false),
+        AccountMeta::new_readonly(*ctx.accounts.user_source_owner.key, true),
+    ];
+
+    let ix = Instruction {
+        program_id: *ctx.accounts.amm_program.key,
+        accounts: ix_accounts,
+        data: data.try_to_vec()?,
+    };
+
+    let custody = Custody::of(&ctx.accounts.user_source_owner, &ctx.accounts.swap_state)?;
+    let accounts = [
+        ctx.accounts.token_program.to_account_info(),
+        ctx.accounts.amm.to_account_info(),
+        ctx.accounts.amm_authority.to_account_info(),
+        ctx.accounts.amm_open_orders.to_account_info(),
+        ctx.accounts.amm_target_orders.to_account_info(),
+        ctx.accounts.pool_coin_token_account.to_account_info(),
+        ctx.accounts.pool_pc_token_account.to_account_info(),
+        ctx.accounts.serum_program.to_account_info(),
+        ctx.accounts.serum_market.to_account_info(),
+        ctx.accounts.serum_bids.to_account_info(),
+        ctx.accounts.serum_asks.to_account_info(),
+        ctx.accounts.serum_event_queue.to_account_info(),
+        ctx.accounts.serum_coin_vault_account.to_account_info(),
+        ctx.accounts.serum_pc_vault_account.to_account_info(),
+        ctx.accounts.serum_vault_signer.to_account_info(),
+        ctx.accounts.user_source_token_account.to_account_info(),
+        ctx.accounts.user_destination_token_account.to_account_info(),
+        ctx.accounts.user_source_owner.to_account_info(),
+        ctx.accounts.amm_program.to_account_info(),
+    ];
+    custody.with_signer(|signer| solana_program::program::invoke_signed(&ix, &accounts, signer))?;
 
     Ok(())
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct RaydiumSwap<'info> {
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(address = RAYDIUM_AMM_V4_PROGRAM_ID @ ErrorCode::UnknownDexProgram)]
+    /// CHECK: This is not dangerous because we check the program id
     pub amm_program: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut, owner = RAYDIUM_AMM_V4_PROGRAM_ID @ ErrorCode::InvalidRaydiumPool)]
+    /// CHECK: The pool state is parsed and checked in `_raydium_swap`
+    pub amm: AccountInfo<'info>,
+    /// CHECK: This is not dangerous because we check the PDA against the pool nonce
     pub amm_authority: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we check it against the pool state
     pub amm_open_orders: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we check it against the pool state
     pub amm_target_orders: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we check it against the pool state
     pub pool_coin_token_account: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we check it against the pool state
     pub pool_pc_token_account: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(constraint = SERUM_PROGRAM_IDS.contains(serum_program.key) @ ErrorCode::UnknownDexProgram)]
+    /// CHECK: This is not dangerous because we check the program id
     pub serum_program: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut)]
+    /// CHECK: This is not dangerous because we check it against the pool state
     pub serum_market: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut)]
+    /// CHECK: Checked against the market by the serum program
     pub serum_bids: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut)]
+    /// CHECK: Checked against the market by the serum program
     pub serum_asks: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut)]
+    /// CHECK: Checked against the market by the serum program
     pub serum_event_queue: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut)]
+    /// CHECK: Checked against the market by the serum program
     pub serum_coin_vault_account: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    #[account(mut)]
+    /// CHECK: Checked against the market by the serum program
     pub serum_pc_vault_account: AccountInfo<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
+    /// CHECK: Checked against the market by the serum program
     pub serum_vault_signer: AccountInfo<'info>,
-    pub user_source_token_account: Account<'info, TokenAccount>,
-    pub user_destination_token_account: Account<'info, TokenAccount>,
-    pub user_source_owner: Signer<'info>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
-    pub token_program: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(
+        mut,
+        token::token_program = token_program,
+        constraint = swap_state.is_custodian(&user_source_token_account.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_source_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::token_program = token_program,
+        constraint = swap_state.is_custodian(&user_destination_token_account.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub user_destination_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: The swap authority or its treasury PDA, checked by `Custody::of`
+    pub user_source_owner: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: This is not dangerous because we check the sysvar id
+    pub instructions: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        constraint = swap_state.is_custodian(user_source_owner.key) @ ErrorCode::InvalidAuthority
+    )]
     pub swap_state: Account<'info, SwapState>,
-}
\ No newline at end of file
+}
@@ -0,0 +1,126 @@
+use anchor_lang::prelude::*;
+use anchor_spl::memo::Memo;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use whirlpool_cpi::{self, state::Whirlpool};
+use crate::error::ErrorCode;
+use crate::state::SwapState;
+use crate::treasury::Custody;
+
+// sqrt price bounds of the whirlpool program, used when no explicit limit is given
+pub const MIN_SQRT_PRICE_X64: u128 = 4295048016;
+pub const MAX_SQRT_PRICE_X64: u128 = 79226673515401279992447579055;
+
+pub fn whirlpool_fee_rate(accounts: &WhirlpoolSwap) -> u64 {
+    accounts.whirlpool.fee_rate as u64
+}
+
+pub fn _whirlpool_swap<'info>(
+    ctx: &Context<'_, '_, '_, 'info, WhirlpoolSwap<'info>>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    sqrt_price_limit: u128,
+    a_to_b: bool,
+) -> Result<()> {
+    let sqrt_price_limit = match (sqrt_price_limit, a_to_b) {
+        (0, true) => MIN_SQRT_PRICE_X64,
+        (0, false) => MAX_SQRT_PRICE_X64,
+        (limit, _) => limit,
+    };
+
+    // swap_v2 takes a token program per mint, which is what lets Token-2022 pools through
+    let cpi_accounts = whirlpool_cpi::cpi::accounts::SwapV2 {
+        token_program_a: ctx.accounts.token_program_a.to_account_info(),
+        token_program_b: ctx.accounts.token_program_b.to_account_info(),
+        memo_program: ctx.accounts.memo_program.to_account_info(),
+        token_authority: ctx.accounts.token_authority.to_account_info(),
+        whirlpool: ctx.accounts.whirlpool.to_account_info(),
+        token_mint_a: ctx.accounts.token_mint_a.to_account_info(),
+        token_mint_b: ctx.accounts.token_mint_b.to_account_info(),
+        token_owner_account_a: ctx.accounts.token_owner_account_a.to_account_info(),
+        token_vault_a: ctx.accounts.token_vault_a.to_account_info(),
+        token_owner_account_b: ctx.accounts.token_owner_account_b.to_account_info(),
+        token_vault_b: ctx.accounts.token_vault_b.to_account_info(),
+        tick_array_0: ctx.accounts.tick_array_0.to_account_info(),
+        tick_array_1: ctx.accounts.tick_array_1.to_account_info(),
+        tick_array_2: ctx.accounts.tick_array_2.to_account_info(),
+        oracle: ctx.accounts.oracle.to_account_info(),
+    };
+
+    let custody = Custody::of(&ctx.accounts.token_authority, &ctx.accounts.swap_state)?;
+    custody.with_signer(|signer| {
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.whirlpool_program.to_account_info(), cpi_accounts, signer);
+
+        whirlpool_cpi::cpi::swap_v2(
+            cpi_ctx,
+            amount_in,
+            minimum_amount_out,
+            sqrt_price_limit,
+            true, // amount_specified_is_input
+            a_to_b,
+            None, // no transfer hook accounts
+        )
+    })?;
+
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct WhirlpoolSwap<'info> {
+    #[account(address = whirlpool_cpi::ID @ ErrorCode::UnknownDexProgram)]
+    /// CHECK: This is not dangerous because we check the program id
+    pub whirlpool_program: AccountInfo<'info>,
+    #[account(address = *token_mint_a.to_account_info().owner)]
+    pub token_program_a: Interface<'info, TokenInterface>,
+    #[account(address = *token_mint_b.to_account_info().owner)]
+    pub token_program_b: Interface<'info, TokenInterface>,
+    pub memo_program: Program<'info, Memo>,
+    /// CHECK: The swap authority or its treasury PDA, checked by `Custody::of`
+    pub token_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub whirlpool: Box<Account<'info, Whirlpool>>,
+    #[account(address = whirlpool.token_mint_a)]
+    pub token_mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = whirlpool.token_mint_b)]
+    pub token_mint_b: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        token::mint = token_mint_a,
+        token::token_program = token_program_a,
+        constraint = swap_state.is_custodian(&token_owner_account_a.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub token_owner_account_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, address = whirlpool.token_vault_a)]
+    pub token_vault_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = token_mint_b,
+        token::token_program = token_program_b,
+        constraint = swap_state.is_custodian(&token_owner_account_b.owner) @ ErrorCode::InvalidTokenOwner
+    )]
+    pub token_owner_account_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut, address = whirlpool.token_vault_b)]
+    pub token_vault_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    /// CHECK: checked by whirlpool_program
+    pub tick_array_0: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: checked by whirlpool_program
+    pub tick_array_1: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: checked by whirlpool_program
+    pub tick_array_2: AccountInfo<'info>,
+    #[account(mut, seeds = [b"oracle", whirlpool.key().as_ref()], bump, seeds::program = whirlpool_program.key())]
+    /// CHECK: checked by whirlpool_program
+    pub oracle: AccountInfo<'info>,
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: This is not dangerous because we check the sysvar id
+    pub instructions: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"swap_state", swap_state.authority.as_ref(), swap_state.input_token.as_ref()],
+        bump = swap_state.bump,
+        constraint = swap_state.is_custodian(token_authority.key) @ ErrorCode::InvalidAuthority
+    )]
+    pub swap_state: Account<'info, SwapState>,
+}
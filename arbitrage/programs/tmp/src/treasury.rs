@@ -0,0 +1,36 @@
+// File: src/treasury.rs
+
+use anchor_lang::prelude::*;
+use crate::error::ErrorCode;
+use crate::state::{SwapState, Treasury};
+
+// Who signs a leg's CPI for the user token accounts: the swap authority itself,
+// or the treasury PDA through `invoke_signed` when the funds are program-custodied.
+pub enum Custody {
+    Authority,
+    Treasury { owner: Pubkey, bump: u8 },
+}
+
+impl Custody {
+    // In treasury mode the operator does not sign the leg itself; `prepare_swap`
+    // only lets legs run inside a window opened by the operator's own `start_swap`.
+    pub fn of(leg_authority: &AccountInfo, swap_state: &SwapState) -> Result<Custody> {
+        require!(swap_state.is_custodian(leg_authority.key), ErrorCode::InvalidAuthority);
+        if swap_state.treasury == Pubkey::default() {
+            require!(leg_authority.is_signer, ErrorCode::InvalidAuthority);
+            return Ok(Custody::Authority);
+        }
+        require_keys_eq!(*leg_authority.owner, crate::ID, ErrorCode::InvalidAuthority);
+        let treasury = Treasury::try_deserialize(&mut &leg_authority.try_borrow_data()?[..])?;
+        // the owner can revoke the operator while its swap states still point here
+        require_keys_eq!(treasury.operator, swap_state.authority, ErrorCode::InvalidAuthority);
+        Ok(Custody::Treasury { owner: treasury.owner, bump: treasury.bump })
+    }
+
+    pub fn with_signer<T>(&self, f: impl FnOnce(&[&[&[u8]]]) -> T) -> T {
+        match self {
+            Custody::Authority => f(&[]),
+            Custody::Treasury { owner, bump } => f(&[&[b"treasury", owner.as_ref(), &[*bump]]]),
+        }
+    }
+}
@@ -69,19 +69,25 @@ describe("tmp", () => {
 
     it("sets up the info pda", async () => {
         const [state_pda, sb] = await anchor.web3.PublicKey.findProgramAddress(
-            [Buffer.from("swap_state")],
+            [Buffer.from("swap_state"), wallet.publicKey.toBuffer(), token.NATIVE_MINT.toBuffer()],
             program.programId
         );    
+        const [arbitrage_state_pda, ab] = await anchor.web3.PublicKey.findProgramAddress(
+            [Buffer.from("arbitrage_state"), wallet.publicKey.toBuffer(), token.NATIVE_MINT.toBuffer()],
+            program.programId
+        );
         console.log("pda:", state_pda.toString())
         let info = await connection.getAccountInfo(state_pda);
         if (info == null) {
             console.log("initializing pda...")
-            await program.rpc.initProgram({
+            await program.rpc.initProgram(token.NATIVE_MINT, {
                 accounts: {
                     swapState: state_pda, 
-                    payer: provider.wallet.publicKey,
+                    arbitrageState: arbitrage_state_pda,
+                    authority: wallet.publicKey,
                     systemProgram: web3.SystemProgram.programId,
-                }
+                },
+                signers: [wallet]
             });
         } else { 
             console.log("pda already initialized...")